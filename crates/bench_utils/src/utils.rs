@@ -1,6 +1,5 @@
 use std::str::FromStr;
 
-use if_chain::if_chain;
 use proc_macro::TokenStream;
 use proc_macro2::{Ident, Span, TokenStream as TokenStream2};
 use quote::{quote, ToTokens};
@@ -20,14 +19,12 @@ pub struct ArrayAssign {
 pub fn parse_arr_assign(input: TokenStream) -> ArrayAssign {
   let expr: syn::ExprAssign = syn::parse(input).unwrap();
 
-  if_chain! {
-    if let syn::Expr::Type(expr_ty) = *expr.left;
-    if let syn::Type::Array(arr_ty) = *expr_ty.ty;
-    then {
+  if let syn::Expr::Type(expr_ty) = *expr.left {
+    if let syn::Type::Array(arr_ty) = *expr_ty.ty {
       return ArrayAssign {
-          var_name: *expr_ty.expr,
-          var_val: *expr.right,
-          num_locations: parse_expr(arr_ty.len),
+        var_name: *expr_ty.expr,
+        var_val: *expr.right,
+        num_locations: parse_expr(arr_ty.len),
       };
     }
   }
@@ -48,25 +45,21 @@ pub struct StructAssign {
 pub fn parse_struct_assign(input: TokenStream) -> StructAssign {
   let expr: syn::ExprAssign = syn::parse(input).unwrap();
 
-  if_chain! {
-    if let syn::Expr::Type(expr_ty) = *expr.left;
-
-    if let syn::Type::Path(struct_ty) = *expr_ty.ty;
-    let ty_segments = struct_ty.path.segments.first().unwrap();
-
-    if let syn::PathArguments::AngleBracketed(generics) = &ty_segments.arguments;
-    let arr_generic = generics.args.first().unwrap();
-
-    if let syn::GenericArgument::Type(syn::Type::Array(arr_ty)) = arr_generic;
-
-    then {
-      return StructAssign {
-        var_name: *expr_ty.expr,
-        struct_name: ty_segments.ident.clone(),
-        field_ty: *arr_ty.elem.clone(),
-        field_val: *expr.right,
-        num_fields: parse_expr(arr_ty.len.clone()),
-      };
+  if let syn::Expr::Type(expr_ty) = *expr.left {
+    if let syn::Type::Path(struct_ty) = *expr_ty.ty {
+      let ty_segments = struct_ty.path.segments.first().unwrap();
+      if let syn::PathArguments::AngleBracketed(generics) = &ty_segments.arguments {
+        let arr_generic = generics.args.first().unwrap();
+        if let syn::GenericArgument::Type(syn::Type::Array(arr_ty)) = arr_generic {
+          return StructAssign {
+            var_name: *expr_ty.expr,
+            struct_name: ty_segments.ident.clone(),
+            field_ty: *arr_ty.elem.clone(),
+            field_val: *expr.right,
+            num_fields: parse_expr(arr_ty.len.clone()),
+          };
+        }
+      }
     }
   }
 
@@ -16,7 +16,6 @@ use flowistry::{
   mir::{borrowck_facts::{self, CachedSimplifedBodyWithFacts}, utils::PlaceExt},
 };
 use glob::glob;
-use rustc_borrowck::BodyWithBorrowckFacts;
 use rustc_hir::{BodyId, ItemKind};
 use rustc_middle::{
   mir::{Location, Place},
@@ -144,7 +143,7 @@ fn criterion_benchmark(c: &mut Criterion) {
 
     // Find bench_utils .so file
     let shared_object = glob(bench_crate_pattern.to_str().unwrap())?
-      .nth(0)
+      .next()
       .context("Failed to find bench_utils shared object")??;
 
     let mut run_bench = |test: (&str, &str)| {
@@ -181,7 +180,7 @@ fn criterion_benchmark(c: &mut Criterion) {
     match std::env::var("FLOWISTRY_BENCH_TEST") {
       Ok(test_file) => {
         let test = TESTS
-          .into_iter()
+          .iter()
           .find(|t| t.1 == test_file)
           .with_context(|| format!("Failed to find test file '{test_file}'"))?;
         run_bench(*test);
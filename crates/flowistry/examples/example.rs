@@ -31,7 +31,6 @@ use flowistry::{
   },
   source_map::{EnclosingHirSpans, Spanner},
 };
-use rustc_borrowck::BodyWithBorrowckFacts;
 use rustc_hir::{BodyId, ItemKind};
 use rustc_middle::{
   mir::{Local, Location, Place},
@@ -27,6 +27,7 @@ impl FromStr for MutabilityMode {
 pub enum ContextMode {
   SigOnly,
   Recurse,
+  Summary,
 }
 
 impl FromStr for ContextMode {
@@ -35,6 +36,7 @@ impl FromStr for ContextMode {
     match s {
       "SigOnly" => Ok(Self::SigOnly),
       "Recurse" => Ok(Self::Recurse),
+      "Summary" => Ok(Self::Summary),
       _ => Err(format!("Could not parse: {s}")),
     }
   }
@@ -44,6 +46,8 @@ impl FromStr for ContextMode {
 pub enum PointerMode {
   Precise,
   Conservative,
+  FlowSensitive,
+  PointsTo,
 }
 
 impl FromStr for PointerMode {
@@ -52,6 +56,93 @@ impl FromStr for PointerMode {
     match s {
       "Precise" => Ok(Self::Precise),
       "Conservative" => Ok(Self::Conservative),
+      "FlowSensitive" => Ok(Self::FlowSensitive),
+      "PointsTo" => Ok(Self::PointsTo),
+      _ => Err(format!("Could not parse: {s}")),
+    }
+  }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Deserialize, Serialize, Hash)]
+pub enum ControlMode {
+  DataAndControl,
+  DataOnly,
+}
+
+impl FromStr for ControlMode {
+  type Err = String;
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "DataAndControl" => Ok(Self::DataAndControl),
+      "DataOnly" => Ok(Self::DataOnly),
+      _ => Err(format!("Could not parse: {s}")),
+    }
+  }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Deserialize, Serialize, Hash)]
+pub enum ExitMode {
+  ReturnOnly,
+  AllExits,
+}
+
+impl FromStr for ExitMode {
+  type Err = String;
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "ReturnOnly" => Ok(Self::ReturnOnly),
+      "AllExits" => Ok(Self::AllExits),
+      _ => Err(format!("Could not parse: {s}")),
+    }
+  }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Deserialize, Serialize, Hash)]
+pub enum InteriorMutabilityMode {
+  Ignore,
+  Track,
+}
+
+impl FromStr for InteriorMutabilityMode {
+  type Err = String;
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "Ignore" => Ok(Self::Ignore),
+      "Track" => Ok(Self::Track),
+      _ => Err(format!("Could not parse: {s}")),
+    }
+  }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Deserialize, Serialize, Hash)]
+pub enum GlobalsMode {
+  Ignore,
+  Track,
+}
+
+impl FromStr for GlobalsMode {
+  type Err = String;
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "Ignore" => Ok(Self::Ignore),
+      "Track" => Ok(Self::Track),
+      _ => Err(format!("Could not parse: {s}")),
+    }
+  }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Deserialize, Serialize, Hash)]
+pub enum SliceMode {
+  Full,
+  Thin,
+}
+
+impl FromStr for SliceMode {
+  type Err = String;
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "Full" => Ok(Self::Full),
+      "Thin" => Ok(Self::Thin),
       _ => Err(format!("Could not parse: {s}")),
     }
   }
@@ -62,6 +153,26 @@ pub struct EvalMode {
   pub mutability_mode: MutabilityMode,
   pub context_mode: ContextMode,
   pub pointer_mode: PointerMode,
+  pub control_mode: ControlMode,
+  /// Whether control dependencies are computed with respect to the returns of a body
+  /// only, or also to the exits where the body panics or diverges. With the latter,
+  /// code that only runs if a call did not panic is control-dependent on the call.
+  pub exit_mode: ExitMode,
+  /// Whether shared references to types with interior mutability, i.e. types
+  /// containing an `UnsafeCell` like `Cell` or `Mutex`, are treated as mutable.
+  pub interior_mutability_mode: InteriorMutabilityMode,
+  /// Whether flows through mutable globals (`static mut`s, statics with interior
+  /// mutability, and thread-locals) are tracked, including across calls to local
+  /// functions. Tracking globals requires the borrowck facts of every local function
+  /// transitively called by the body.
+  pub globals_mode: GlobalsMode,
+  /// Whether a place depends on the pointers used to reach it. A thin slice only
+  /// contains the statements producing the values of a place, and not those computing
+  /// the pointers dereferenced in the place (see Sridharan et al., "Thin Slicing").
+  pub slice_mode: SliceMode,
+  /// The maximum projection depth of pointers within arguments whose pointees are
+  /// tracked separately. Pointees of deeper pointers are summarized by their prefix.
+  pub depth_limit: usize,
 }
 
 impl Default for EvalMode {
@@ -70,6 +181,12 @@ impl Default for EvalMode {
       mutability_mode: MutabilityMode::DistinguishMut,
       context_mode: ContextMode::SigOnly,
       pointer_mode: PointerMode::Precise,
+      control_mode: ControlMode::DataAndControl,
+      exit_mode: ExitMode::ReturnOnly,
+      interior_mutability_mode: InteriorMutabilityMode::Ignore,
+      globals_mode: GlobalsMode::Ignore,
+      slice_mode: SliceMode::Full,
+      depth_limit: 2,
     }
   }
 }
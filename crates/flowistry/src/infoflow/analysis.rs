@@ -1,20 +1,27 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{
+  cell::{Cell, RefCell},
+  rc::Rc,
+};
 
 use log::{debug, trace, warn};
 use rustc_data_structures::fx::{FxHashMap as HashMap, FxHashSet as HashSet};
-use rustc_hir::{def_id::DefId, BodyId};
+use rustc_hir::{def_id::DefId, BodyId, GeneratorKind};
 use rustc_middle::{
   mir::{visit::Visitor, *},
-  ty::TyCtxt,
+  ty::{subst::SubstsRef, TyCtxt, TyKind},
 };
 use rustc_mir_dataflow::{Analysis, AnalysisDomain, Forward, JoinSemiLattice};
 
 use super::{
+  kinds::{DependencyKind, DependencyKinds, KindSets},
+  models,
   mutation::{ModularMutationVisitor, MutationStatus},
   FlowResults,
 };
 use crate::{
-  extensions::{is_extension_active, ContextMode, MutabilityMode},
+  extensions::{
+    is_extension_active, ContextMode, ControlMode, MutabilityMode, SliceMode,
+  },
   indexed::{
     impls::{LocationDomain, LocationSet},
     IndexMatrix, IndexSet, IndexedDomain, RefSet,
@@ -22,7 +29,7 @@ use crate::{
   mir::{
     aliases::Aliases,
     control_dependencies::ControlDependencies,
-    utils::{OperandExt, PlaceExt},
+    utils::{self, OperandExt, PlaceExt},
   },
 };
 
@@ -58,16 +65,35 @@ pub trait FlowDomain<'tcx>: JoinSemiLattice + Clone {
   fn matrix(&self) -> &FlowDomainMatrix<'tcx>;
   fn matrix_mut(&mut self) -> &mut FlowDomainMatrix<'tcx>;
   fn row<'a>(&'a self, row: Place<'tcx>) -> IndexSet<Location, RefSet<'a, Location>>;
+  /// Adds the dependencies `from` of a mutation at `at` to `row`, where `kinds` labels
+  /// the dependencies if the domain tracks their kinds.
   fn union_after<S: crate::indexed::ToSet<Location>>(
     &mut self,
     row: Place<'tcx>,
     from: &IndexSet<Location, S>,
+    kinds: Option<&KindSets>,
     at: Location,
   ) -> bool;
   fn from_location_domain(dom: &Rc<LocationDomain>) -> Self;
   fn include(&mut self, row: Place<'tcx>, at: Location) -> bool;
-  fn mutated_values_for(aliases: &Aliases<'_, 'tcx>, place: Place<'tcx>) -> HashSet<Place<'tcx>> {
-    aliases.conflicts(place).to_owned()
+  /// Adds the location `at` of an argument to `row` at the start of the body.
+  fn include_arg(&mut self, row: Place<'tcx>, at: Location) -> bool {
+    self.matrix_mut().insert(row, at)
+  }
+  fn clear_row(&mut self, row: Place<'tcx>) {
+    self.matrix_mut().clear_row(row);
+  }
+  /// The kinds of the dependencies, if the domain tracks them
+  /// (see [`KindedFlowDomain`](super::KindedFlowDomain)).
+  fn kinds(&self) -> Option<&DependencyKinds<'tcx>> {
+    None
+  }
+  fn mutated_values_for(
+    aliases: &Aliases<'_, 'tcx>,
+    place: Place<'tcx>,
+    location: Location,
+  ) -> HashSet<Place<'tcx>> {
+    aliases.conflicts_at(place, location).into_owned()
   }
 }
 
@@ -85,6 +111,7 @@ impl<'tcx> FlowDomain<'tcx> for TransitiveFlowDomain<'tcx> {
     &mut self,
     row: Place<'tcx>,
     from: &IndexSet<Location, S>,
+    _kinds: Option<&KindSets>,
     _at: Location,
   ) -> bool {
     self.union_into_row(row, from)
@@ -111,6 +138,7 @@ impl<'tcx> FlowDomain<'tcx> for NonTransitiveFlowDomain<'tcx> {
     &mut self,
     row: Place<'tcx>,
     _from: &IndexSet<Location, S>,
+    _kinds: Option<&KindSets>,
     at: Location,
   ) -> bool {
     self.override_(row, at)
@@ -124,7 +152,11 @@ impl<'tcx> FlowDomain<'tcx> for NonTransitiveFlowDomain<'tcx> {
   fn include(&mut self, row: Place<'tcx>, at: Location) -> bool {
     self.override_(row, at)
   }
-  fn mutated_values_for(aliases: &Aliases<'_, 'tcx>, place: Place<'tcx>) -> HashSet<Place<'tcx>> {
+  fn mutated_values_for(
+    aliases: &Aliases<'_, 'tcx>,
+    place: Place<'tcx>,
+    _location: Location,
+  ) -> HashSet<Place<'tcx>> {
     aliases.children(place)
   }
 }
@@ -147,13 +179,57 @@ impl<'tcx> JoinSemiLattice for NonTransitiveFlowDomain<'tcx> {
   }
 }
 
+type RecurseCache<'a, 'tcx, D> =
+  RefCell<HashMap<(BodyId, Option<SubstsRef<'tcx>>), FlowResults<'a, 'tcx, D>>>;
+
 pub struct FlowAnalysis<'a, 'tcx, D: FlowDomain<'tcx>> {
   pub tcx: TyCtxt<'tcx>,
   pub def_id: DefId,
   pub body: &'a Body<'tcx>,
   pub control_dependencies: ControlDependencies,
   pub aliases: Aliases<'a, 'tcx>,
-  crate recurse_cache: RefCell<HashMap<BodyId, FlowResults<'a, 'tcx, D>>>,
+  crate recurse_cache: RecurseCache<'a, 'tcx, D>,
+
+  // The types that the body's generic parameters are instantiated with, if known
+  crate substs: Option<SubstsRef<'tcx>>,
+
+  // The locals holding the task context passed to an async body when it is resumed
+  task_context_locals: HashSet<Local>,
+
+  // Whether the effects of the call being applied are approximated from its
+  // signature, which labels the dependencies it adds (see DependencyKind::Call)
+  approximating_call: Cell<bool>,
+}
+
+/// Returns the locals of an async body that hold its task context: the resume argument
+/// (the second argument, after the generator itself), the places that each `.await`
+/// stores the resumed task context into, and the locals that these are moved into.
+fn task_context_locals(body: &Body<'_>) -> HashSet<Local> {
+  let mut locals = HashSet::from_iter([Local::from_usize(2)]);
+  for data in body.basic_blocks() {
+    if let TerminatorKind::Yield { resume_arg, .. } = &data.terminator().kind {
+      locals.extend(resume_arg.as_local());
+    }
+  }
+
+  loop {
+    let mut changed = false;
+    for data in body.basic_blocks() {
+      for statement in &data.statements {
+        if let StatementKind::Assign(box (dst, Rvalue::Use(op))) = &statement.kind {
+          let src = op.place().and_then(|src| src.as_local());
+          if let (Some(dst), Some(src)) = (dst.as_local(), src) {
+            if locals.contains(&src) {
+              changed |= locals.insert(dst);
+            }
+          }
+        }
+      }
+    }
+    if !changed {
+      return locals;
+    }
+  }
 }
 
 impl<'a, 'tcx, D: FlowDomain<'tcx>> FlowAnalysis<'a, 'tcx, D> {
@@ -165,6 +241,14 @@ impl<'a, 'tcx, D: FlowDomain<'tcx>> FlowAnalysis<'a, 'tcx, D> {
     control_dependencies: ControlDependencies,
   ) -> Self {
     let recurse_cache = RefCell::new(HashMap::default());
+
+    let is_async = matches!(tcx.generator_kind(def_id), Some(GeneratorKind::Async(_)));
+    let task_context_locals = if is_async {
+      task_context_locals(body)
+    } else {
+      HashSet::default()
+    };
+
     FlowAnalysis {
       tcx,
       def_id,
@@ -172,9 +256,24 @@ impl<'a, 'tcx, D: FlowDomain<'tcx>> FlowAnalysis<'a, 'tcx, D> {
       aliases,
       control_dependencies,
       recurse_cache,
+      substs: None,
+      task_context_locals,
+      approximating_call: Cell::new(false),
     }
   }
 
+  /// Returns true if `place` holds the task context of an async body.
+  ///
+  /// The task context is replaced by the executor every time the body is resumed
+  /// after an `.await`, and only passes the executor's state to the futures being
+  /// polled. If it were tracked like other places, then every `.await` would depend
+  /// on whether all the previous `.await`s were ready on their first poll.
+  fn is_task_context(&self, place: Place<'tcx>) -> bool {
+    place
+      .as_local()
+      .map_or(false, |local| self.task_context_locals.contains(&local))
+  }
+
   pub fn location_domain(&self) -> &Rc<LocationDomain> {
     self.aliases.location_domain()
   }
@@ -183,82 +282,186 @@ impl<'a, 'tcx, D: FlowDomain<'tcx>> FlowAnalysis<'a, 'tcx, D> {
     &self,
     state: &mut D,
     mutated: Place<'tcx>,
-    inputs: &[(Place<'tcx>, Option<PlaceElem<'tcx>>)],
+    inputs: &[(Place<'tcx>, Option<&'tcx [PlaceElem<'tcx>]>)],
     location: Location,
     mutation_status: MutationStatus,
   ) {
     debug!("  Applying mutation to {mutated:?} with inputs {inputs:?}");
     let location_domain = self.location_domain();
 
+    if self.is_task_context(mutated) {
+      let mutated = self.aliases.normalize(mutated);
+      state.clear_row(mutated);
+      state.include(mutated, location);
+      return;
+    }
+
     let all_aliases = &self.aliases;
-    let mutated_aliases = all_aliases.aliases(mutated);
+    let mutated_aliases = all_aliases.aliases_at(mutated, location);
     trace!("    Mutated aliases: {mutated_aliases:?}");
     assert!(!mutated_aliases.is_empty());
 
-    // Clear sub-places of mutated place (if sound to do so)
+    // Clear sub-places of mutated place (if sound to do so). A mutation of an
+    // unknown element of an array does not overwrite the other elements.
     if matches!(mutation_status, MutationStatus::Definitely) && mutated_aliases.len() == 1
     {
       let mutated_direct = mutated_aliases.iter().next().unwrap();
-      for sub in all_aliases.children(*mutated_direct).iter() {
-        state.matrix_mut().clear_row(all_aliases.normalize(*sub));
+      let unknown_index = all_aliases
+        .normalize(*mutated_direct)
+        .projection
+        .iter()
+        .any(|elem| matches!(elem, ProjectionElem::Index(_)));
+      if !unknown_index {
+        for sub in all_aliases.children(*mutated_direct).iter() {
+          state.clear_row(all_aliases.normalize(*sub));
+        }
       }
     }
 
     let mut input_location_deps = LocationSet::new(location_domain);
     input_location_deps.insert(location);
-
-    let add_deps = |place: Place<'tcx>, location_deps: &mut LocationSet| {
-      let reachable_values = all_aliases.reachable_values(place, Mutability::Not);
-      let provenance =
-        place
-          .refs_in_projection()
-          .into_iter()
-          .flat_map(|(place_ref, _)| {
-            all_aliases
-              .aliases(Place::from_ref(place_ref, self.tcx))
-              .iter()
-          });
-      for relevant in reachable_values.iter().chain(provenance) {
-        let deps = state.row(all_aliases.normalize(*relevant));
+    let new_kind_sets = || {
+      state
+        .kinds()
+        .is_some()
+        .then(|| KindSets::new(location_domain))
+    };
+    let mut input_kind_sets = new_kind_sets();
+    if let Some(kind_sets) = &mut input_kind_sets {
+      kind_sets.insert(DependencyKind::Data, location);
+    }
+    let approximating_call = self.approximating_call.get();
+
+    // The discriminant of an enum is read from the enum place alone, so it does not
+    // depend on the payloads of the enum's variants (see Aliases::reachable_values)
+    let reads_discriminant = matches!(
+      self
+        .body
+        .stmt_at(location)
+        .left()
+        .map(|statement| &statement.kind),
+      Some(StatementKind::Assign(box (_, Rvalue::Discriminant(_))))
+    );
+
+    // A thin slice excludes the dependencies of the pointers used to reach a place
+    let thin = is_extension_active(|mode| mode.slice_mode == SliceMode::Thin);
+    let add_deps = |place: Place<'tcx>,
+                    location_deps: &mut LocationSet,
+                    kind_sets: &mut Option<KindSets>,
+                    control: bool| {
+      let reachable_values = if reads_discriminant {
+        all_aliases.aliases_at(place, location)
+      } else {
+        all_aliases.reachable_values_at(place, Mutability::Not, location)
+      };
+      let provenance = (!thin)
+        .then(|| place.refs_in_projection())
+        .into_iter()
+        .flatten()
+        .flat_map(|(place_ref, _)| {
+          all_aliases
+            .aliases_at(Place::from_ref(place_ref, self.tcx), location)
+            .into_owned()
+        });
+      let relevant_places = reachable_values
+        .iter()
+        .map(|relevant| (*relevant, relevant.local != place.local))
+        .chain(provenance.map(|pointer| (pointer, true)));
+      for (relevant, through_alias) in relevant_places {
+        let relevant = all_aliases.normalize(relevant);
+        let deps = state.row(relevant);
         trace!("    For relevant {relevant:?} for input {place:?} adding deps {deps:?}");
         location_deps.union(&deps);
+
+        if let (Some(kind_sets), Some(kinds)) = (kind_sets.as_mut(), state.kinds()) {
+          let edge_kinds = [
+            (through_alias, DependencyKind::Alias),
+            (control, DependencyKind::Control),
+            (approximating_call, DependencyKind::Call),
+          ];
+          let direct = edge_kinds.iter().all(|(is_edge, _)| !is_edge);
+          kind_sets.inherit(kinds, relevant, direct);
+          for (_, kind) in edge_kinds.into_iter().filter(|(is_edge, _)| *is_edge) {
+            kind_sets.label(kind, &deps);
+          }
+        }
       }
     };
 
     // Add deps of mutated to include provenance of mutated pointers
-    add_deps(mutated, &mut input_location_deps);
+    add_deps(
+      mutated,
+      &mut input_location_deps,
+      &mut input_kind_sets,
+      false,
+    );
 
     // Add deps of all inputs
     let mut children = Vec::new();
-    for (place, elem) in inputs.iter() {
-      add_deps(*place, &mut input_location_deps);
-
-      // If the input is associated to a specific projection of the mutated
-      // place, then save that input's dependencies with the projection
-      if let Some(elem) = elem {
-        let mut projection = mutated.projection.to_vec();
-        projection.push(*elem);
-        let mut child_deps = LocationSet::new(location_domain);
-        add_deps(*place, &mut child_deps);
-        children.push((
-          Place::make(mutated.local, &projection, self.tcx),
-          child_deps,
-        ));
+    for (place, projection) in inputs.iter() {
+      match projection {
+        // If the input is associated to a specific projection of the mutated
+        // place, then save that input's dependencies with the projection
+        Some(projection) => {
+          let mut child_deps = LocationSet::new(location_domain);
+          let mut child_kind_sets = new_kind_sets();
+          add_deps(*place, &mut child_deps, &mut child_kind_sets, false);
+
+          // The payload of an enum variant is not a dependency of the enum's
+          // discriminant, which is tracked by the mutated place itself
+          let is_variant_field = projection
+            .iter()
+            .any(|elem| matches!(elem, ProjectionElem::Downcast(..)));
+          if !is_variant_field {
+            input_location_deps.union(&child_deps);
+            if let (Some(input), Some(child)) = (&mut input_kind_sets, &child_kind_sets) {
+              input.union(child);
+            }
+          }
+
+          let child = mutated.project_deeper(projection, self.tcx);
+          children.push((child, child_deps, child_kind_sets));
+        }
+        None => add_deps(
+          *place,
+          &mut input_location_deps,
+          &mut input_kind_sets,
+          false,
+        ),
       }
     }
 
-    // Add control dependencies
-    let controlled_by = self.control_dependencies.dependent_on(location.block);
+    // Add control dependencies, unless implicit flows are excluded
+    let data_only =
+      is_extension_active(|mode| mode.control_mode == ControlMode::DataOnly);
+    let controlled_by = if data_only {
+      None
+    } else {
+      self.control_dependencies.dependent_on(location.block)
+    };
     let body = self.body;
     for block in controlled_by.into_iter().flat_map(|set| set.iter()) {
       input_location_deps.insert(body.terminator_loc(block));
+      if let Some(kind_sets) = &mut input_kind_sets {
+        kind_sets.insert(DependencyKind::Control, body.terminator_loc(block));
+      }
 
-      // Include dependencies of the switch's operand
+      // Include dependencies of the switch's operand, or of the operands that
+      // determine whether a call or assertion unwinds (see ExitMode::AllExits)
       let terminator = body.basic_blocks()[block].terminator();
-      if let TerminatorKind::SwitchInt { discr, .. } = &terminator.kind {
-        if let Some(discr_place) = discr.to_place() {
-          add_deps(discr_place, &mut input_location_deps);
+      let operands = match &terminator.kind {
+        TerminatorKind::SwitchInt { discr, .. } => vec![discr],
+        TerminatorKind::Call { func, args, .. } => {
+          args.iter().chain([func]).collect::<Vec<_>>()
         }
+        TerminatorKind::Assert { cond, .. } => vec![cond],
+        _ => Vec::new(),
+      };
+      for place in operands
+        .into_iter()
+        .filter_map(|operand| operand.to_place())
+      {
+        add_deps(place, &mut input_location_deps, &mut input_kind_sets, true);
       }
     }
 
@@ -273,33 +476,47 @@ impl<'a, 'tcx, D: FlowDomain<'tcx>> FlowAnalysis<'a, 'tcx, D> {
       }
 
       // Then for constructor arguments that were places, add dependencies of those places.
-      for (child, deps) in children {
-        state.union_after(all_aliases.normalize(child), &deps, location);
+      for (child, deps, kind_sets) in children {
+        let child = all_aliases.normalize(child);
+        state.union_after(child, &deps, kind_sets.as_ref(), location);
       }
 
       // Finally add input_location_deps *JUST* to mutated, not conflicts of mutated.
+      let mutated = all_aliases.normalize(mutated);
       state.union_after(
-        all_aliases.normalize(mutated),
+        mutated,
         &input_location_deps,
+        input_kind_sets.as_ref(),
         location,
       );
     } else {
       // Union dependencies into all conflicting places of the mutated place
-      let mut mutable_conflicts = D::mutated_values_for(all_aliases, mutated);
+      let mut mutable_conflicts = D::mutated_values_for(all_aliases, mutated, location);
 
       // Remove any conflicts that aren't actually mutable, e.g. if x : &T ends up
       // as an alias of y: &mut T. See test function_lifetime_alias_mut for an example.
+      // Shared references to interior mutable types are still mutable, as are
+      // the (mutable) globals behind shared references.
       let ignore_mut =
         is_extension_active(|mode| mode.mutability_mode == MutabilityMode::IgnoreMut);
       if !ignore_mut {
         let body = self.body;
-        let tcx = self.tcx;
+        let (tcx, def_id) = (self.tcx, self.def_id);
+        let globals = all_aliases.globals();
         mutable_conflicts = mutable_conflicts
           .iter()
           .filter(|place| {
+            if globals.is_global(**place) {
+              return true;
+            }
             place.iter_projections().all(|(sub_place, _)| {
               let ty = sub_place.ty(body.local_decls(), tcx).ty;
-              !matches!(ty.ref_mutability(), Some(Mutability::Not))
+              match ty.kind() {
+                TyKind::Ref(_, pointee, Mutability::Not) => {
+                  utils::is_interior_mutable(tcx, def_id, *pointee)
+                }
+                _ => true,
+              }
             })
           })
           .copied()
@@ -310,7 +527,13 @@ impl<'a, 'tcx, D: FlowDomain<'tcx>> FlowAnalysis<'a, 'tcx, D> {
       debug!("    with deps {input_location_deps:?}");
 
       for place in mutable_conflicts.into_iter() {
-        state.union_after(all_aliases.normalize(place), &input_location_deps, location);
+        let place = all_aliases.normalize(place);
+        state.union_after(
+          place,
+          &input_location_deps,
+          input_kind_sets.as_ref(),
+          location,
+        );
       }
     }
   }
@@ -327,14 +550,10 @@ impl<'a, 'tcx, D: FlowDomain<'tcx>> AnalysisDomain<'tcx> for FlowAnalysis<'a, 't
 
   fn initialize_start_block(&self, _body: &Body<'tcx>, state: &mut Self::Domain) {
     for (arg, loc) in self.aliases.all_args() {
+      let loc = *self.location_domain().value(loc);
       for place in self.aliases.conflicts(arg) {
-        debug!(
-          "arg={arg:?} / place={place:?} / loc={:?}",
-          self.location_domain().value(loc)
-        );
-        state
-          .matrix_mut()
-          .insert(self.aliases.normalize(*place), loc);
+        debug!("arg={arg:?} / place={place:?} / loc={loc:?}");
+        state.include_arg(self.aliases.normalize(*place), loc);
       }
     }
   }
@@ -350,7 +569,7 @@ impl<'a, 'tcx, D: FlowDomain<'tcx>> Analysis<'tcx> for FlowAnalysis<'a, 'tcx, D>
     ModularMutationVisitor::new(
       &self.aliases,
       |mutated: Place<'tcx>,
-       inputs: &[(Place<'tcx>, Option<PlaceElem<'tcx>>)],
+       inputs: &[(Place<'tcx>, Option<&'tcx [PlaceElem<'tcx>]>)],
        location: Location,
        mutation_status: MutationStatus| {
         self.transfer_function(state, mutated, inputs, location, mutation_status)
@@ -365,23 +584,41 @@ impl<'a, 'tcx, D: FlowDomain<'tcx>> Analysis<'tcx> for FlowAnalysis<'a, 'tcx, D>
     terminator: &Terminator<'tcx>,
     location: Location,
   ) {
-    if matches!(terminator.kind, TerminatorKind::Call { .. })
-      && is_extension_active(|mode| mode.context_mode == ContextMode::Recurse)
-      && self.recurse_into_call(state, &terminator.kind, location)
-    {
-      return;
-    }
+    // Summaries and recursion only relate a call's arguments and return value,
+    // so the effects of the call on globals are applied separately
+    let applied_call = matches!(terminator.kind, TerminatorKind::Call { .. })
+      && (self.apply_external_summary(state, &terminator.kind, location)
+        || (is_extension_active(|mode| mode.context_mode == ContextMode::Recurse)
+          && self.recurse_into_call(state, &terminator.kind, location))
+        || (is_extension_active(|mode| mode.context_mode == ContextMode::Summary)
+          && self.apply_call_summary(state, &terminator.kind, location)));
+
+    // A call that is not modeled is approximated from its signature
+    let approximating_call = !applied_call
+      && state.kinds().is_some()
+      && match &terminator.kind {
+        TerminatorKind::Call { func, .. } => {
+          models::get_model(self.tcx, self.def_id, func).is_none()
+        }
+        _ => false,
+      };
+    self.approximating_call.set(approximating_call);
 
-    ModularMutationVisitor::new(
+    let mut visitor = ModularMutationVisitor::new(
       &self.aliases,
       |mutated: Place<'tcx>,
-       inputs: &[(Place<'tcx>, Option<PlaceElem<'tcx>>)],
+       inputs: &[(Place<'tcx>, Option<&'tcx [PlaceElem<'tcx>]>)],
        location: Location,
        mutation_status: MutationStatus| {
         self.transfer_function(state, mutated, inputs, location, mutation_status)
       },
-    )
-    .visit_terminator(terminator, location);
+    );
+    if applied_call {
+      visitor.visit_call_globals(&terminator.kind, location);
+    } else {
+      visitor.visit_terminator(terminator, location);
+    }
+    self.approximating_call.set(false);
   }
 
   fn apply_call_return_effect(
@@ -0,0 +1,640 @@
+//! On-disk cache of information flow results.
+//!
+//! Analyzing a function is expensive relative to the time it takes to answer an IDE query,
+//! so if a [`CACHE_DIR`] is set, then [`compute_flow`](super::compute_flow) and
+//! [`compute_flow_with_kinds`](super::compute_flow_with_kinds) save their results in a
+//! rustc-independent form (see [`CachedResults`]) and reuse them on later runs.
+//!
+//! Cached results are keyed by a stable hash of the function's MIR, the current
+//! [`EvalMode`](crate::extensions::EvalMode), and the exported summaries of other crates
+//! (see [`export_summaries`](super::summary::export_summaries)). Results are only cached
+//! in [`ContextMode::SigOnly`], since otherwise they would depend on the bodies of callees.
+//! Even then, they depend on the `flows` annotations of callees and, when globals are
+//! tracked, on the globals that local callees access, which are also part of the key.
+//!
+//! The alias analysis of cached results is rebuilt from its loans alone, so results are
+//! not cached in the pointer modes that also use loan liveness or points-to information.
+
+use std::{
+  fs,
+  hash::Hash,
+  path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Result};
+use fluid_let::fluid_let;
+use log::{debug, warn};
+use rustc_data_structures::{
+  fingerprint::Fingerprint,
+  fx::{FxHashMap as HashMap, FxHashSet as HashSet},
+  stable_hasher::{HashStable, StableHasher},
+};
+use rustc_hir::{def_id::DefId, BodyId};
+use rustc_index::vec::{Idx, IndexVec};
+use rustc_middle::{
+  mir::{tcx::PlaceTy, *},
+  ty::{RegionVid, TyCtxt},
+};
+use rustc_mir_dataflow::JoinSemiLattice;
+use rustc_span::Symbol;
+use rustc_target::abi::VariantIdx;
+use serde::{Deserialize, Serialize};
+
+use super::{
+  models, summary, FlowAnalysis, FlowDomain, FlowDomainMatrix, FlowResults,
+  TransitiveFlowDomain, BODY_STACK,
+};
+use crate::{
+  block_timer,
+  extensions::{is_extension_active, ContextMode, GlobalsMode, PointerMode, EVAL_MODE},
+  indexed::{
+    impls::{LocationDomain, LocationIndex, LocationSet},
+    IndexedDomain,
+  },
+  mir::{
+    aliases::{Aliases, LoanMap},
+    borrowck_facts::CachedSimplifedBodyWithFacts,
+    call_graph::local_callee,
+    control_dependencies::ControlDependencies,
+    engine::AnalysisResults,
+    globals,
+    utils::{self, PlaceExt},
+  },
+};
+
+fluid_let!(pub static CACHE_DIR: PathBuf);
+
+/// A [`FlowDomain`] whose states can be cached as a list of matrices.
+crate trait CachedDomain<'tcx>: FlowDomain<'tcx> + JoinSemiLattice {
+  /// Distinguishes the cached results of each domain for the same body.
+  const NAME: &'static str;
+
+  /// Returns the matrices that make up the state.
+  fn matrices(&self) -> Vec<&FlowDomainMatrix<'tcx>>;
+
+  /// Rebuilds a state from the matrices returned by [`CachedDomain::matrices`],
+  /// returning `None` if they do not make up a state.
+  fn from_matrices(matrices: Vec<FlowDomainMatrix<'tcx>>) -> Option<Self>;
+}
+
+impl<'tcx> CachedDomain<'tcx> for TransitiveFlowDomain<'tcx> {
+  const NAME: &'static str = "flow";
+
+  fn matrices(&self) -> Vec<&FlowDomainMatrix<'tcx>> {
+    vec![self]
+  }
+
+  fn from_matrices(matrices: Vec<FlowDomainMatrix<'tcx>>) -> Option<Self> {
+    let [matrix]: [_; 1] = matrices.try_into().ok()?;
+    Some(matrix)
+  }
+}
+
+/// A projection element of a [`CachedPlace`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+enum CachedElem {
+  Deref,
+  Field(usize),
+  Index(usize),
+  ConstantIndex {
+    offset: u64,
+    min_length: u64,
+    from_end: bool,
+  },
+  Subslice {
+    from: u64,
+    to: u64,
+    from_end: bool,
+  },
+  Downcast(Option<String>, usize),
+}
+
+/// A [`Place`] without any types, which are recomputed from the body's locals.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+struct CachedPlace {
+  local: usize,
+  projection: Vec<CachedElem>,
+}
+
+impl CachedPlace {
+  fn from_place(place: Place<'_>) -> Self {
+    let projection = place
+      .projection
+      .iter()
+      .map(|elem| match elem {
+        ProjectionElem::Deref => CachedElem::Deref,
+        ProjectionElem::Field(field, _) => CachedElem::Field(field.as_usize()),
+        ProjectionElem::Index(local) => CachedElem::Index(local.as_usize()),
+        ProjectionElem::ConstantIndex {
+          offset,
+          min_length,
+          from_end,
+        } => CachedElem::ConstantIndex {
+          offset,
+          min_length,
+          from_end,
+        },
+        ProjectionElem::Subslice { from, to, from_end } => {
+          CachedElem::Subslice { from, to, from_end }
+        }
+        ProjectionElem::Downcast(name, variant) => {
+          CachedElem::Downcast(name.map(|name| name.to_string()), variant.as_usize())
+        }
+      })
+      .collect();
+
+    CachedPlace {
+      local: place.local.as_usize(),
+      projection,
+    }
+  }
+
+  fn to_place<'tcx>(&self, tcx: TyCtxt<'tcx>, body: &Body<'tcx>) -> Option<Place<'tcx>> {
+    let local = Local::from_usize(self.local);
+    let mut ty = PlaceTy::from_ty(body.local_decls().get(local)?.ty);
+    let mut projection = Vec::new();
+    for elem in &self.projection {
+      let elem = match elem {
+        CachedElem::Deref => {
+          ty.ty.builtin_deref(true)?;
+          ProjectionElem::Deref
+        }
+        CachedElem::Field(i) => {
+          let field = Field::from_usize(*i);
+          ProjectionElem::Field(field, utils::field_ty(tcx, ty, field)?)
+        }
+        CachedElem::Index(local) => {
+          ty.ty.builtin_index()?;
+          ProjectionElem::Index(Local::from_usize(*local))
+        }
+        CachedElem::ConstantIndex {
+          offset,
+          min_length,
+          from_end,
+        } => {
+          ty.ty.builtin_index()?;
+          ProjectionElem::ConstantIndex {
+            offset: *offset,
+            min_length: *min_length,
+            from_end: *from_end,
+          }
+        }
+        CachedElem::Subslice { from, to, from_end } => {
+          ty.ty.builtin_index()?;
+          ProjectionElem::Subslice {
+            from: *from,
+            to: *to,
+            from_end: *from_end,
+          }
+        }
+        CachedElem::Downcast(name, i) => {
+          let adt_def = ty.ty.ty_adt_def()?;
+          if !adt_def.is_enum() || *i >= adt_def.variants().len() {
+            return None;
+          }
+          ProjectionElem::Downcast(
+            name.as_ref().map(|name| Symbol::intern(name)),
+            VariantIdx::from_usize(*i),
+          )
+        }
+      };
+      ty = ty.projection_ty(tcx, elem);
+      projection.push(elem);
+    }
+
+    Some(Place::make(local, &projection, tcx))
+  }
+}
+
+/// A table of places referenced by index, so each place is only serialized once.
+#[derive(Default, Serialize, Deserialize)]
+struct PlaceTable {
+  places: Vec<CachedPlace>,
+  #[serde(skip)]
+  indices: HashMap<CachedPlace, usize>,
+}
+
+impl PlaceTable {
+  fn insert(&mut self, place: Place<'_>) -> usize {
+    let cached = CachedPlace::from_place(place);
+    let places = &mut self.places;
+    *self.indices.entry(cached).or_insert_with_key(|cached| {
+      places.push(cached.clone());
+      places.len() - 1
+    })
+  }
+
+  /// Converts each place in the table back into a [`Place`]. If `normalize` is true,
+  /// then the places are normalized (see [`PlaceExt::normalize`]).
+  fn to_places<'tcx>(
+    &self,
+    tcx: TyCtxt<'tcx>,
+    def_id: DefId,
+    body: &Body<'tcx>,
+    normalize: bool,
+  ) -> Option<Vec<Place<'tcx>>> {
+    self
+      .places
+      .iter()
+      .map(|place| {
+        let place = place.to_place(tcx, body)?;
+        Some(if normalize {
+          place.normalize(tcx, def_id)
+        } else {
+          place
+        })
+      })
+      .collect()
+  }
+}
+
+/// A rustc-independent representation of [`FlowResults`].
+#[derive(Serialize, Deserialize)]
+pub struct CachedResults {
+  key: String,
+
+  /// Places in the loans of [`Aliases`].
+  loan_places: PlaceTable,
+  loans: Vec<(usize, Vec<(usize, bool)>)>,
+
+  /// (Normalized) places in the rows of the flow domain.
+  row_places: PlaceTable,
+  /// For each real location in the body, the rows of each matrix of the flow domain
+  /// at that location (see [`CachedDomain::matrices`]).
+  state: Vec<Vec<CachedRows>>,
+}
+
+/// The rows of a matrix, as a list of place indices paired with location indices.
+type CachedRows = Vec<(usize, Vec<usize>)>;
+
+impl CachedResults {
+  fn from_results<'tcx, D: CachedDomain<'tcx>>(
+    key: String,
+    results: &FlowResults<'_, 'tcx, D>,
+  ) -> Self {
+    let aliases = &results.analysis.aliases;
+    let mut loan_places = PlaceTable::default();
+    let loans = aliases
+      .loans()
+      .iter()
+      .map(|(region, loans)| {
+        let loans = loans
+          .iter()
+          .map(|(place, mutability)| {
+            (loan_places.insert(*place), *mutability == Mutability::Mut)
+          })
+          .collect();
+        (region.as_usize(), loans)
+      })
+      .collect();
+
+    let location_domain = results.analysis.location_domain();
+    let mut row_places = PlaceTable::default();
+    let state = (0 .. location_domain.num_real_locations())
+      .map(|index| {
+        let location = *location_domain.value(LocationIndex::new(index));
+        let matrices = results.state_at(location).matrices();
+        matrices
+          .into_iter()
+          .map(|matrix| {
+            matrix
+              .rows()
+              .map(|(place, deps)| {
+                let deps = deps.indices().map(|index| index.index()).collect();
+                (row_places.insert(place), deps)
+              })
+              .collect()
+          })
+          .collect()
+      })
+      .collect();
+
+    CachedResults {
+      key,
+      loan_places,
+      loans,
+      row_places,
+      state,
+    }
+  }
+
+  /// Reconstructs [`FlowResults`] for the given body, returning `None` if the cached
+  /// results do not fit the body.
+  fn to_results<'a, 'tcx, D: CachedDomain<'tcx>>(
+    &self,
+    tcx: TyCtxt<'tcx>,
+    def_id: DefId,
+    body: &'a Body<'tcx>,
+  ) -> Option<FlowResults<'a, 'tcx, D>> {
+    let loan_places = self.loan_places.to_places(tcx, def_id, body, false)?;
+    let mut loans = LoanMap::default();
+    for (region, region_loans) in &self.loans {
+      let region_loans = region_loans
+        .iter()
+        .map(|(place, is_mut)| {
+          let mutability = if *is_mut {
+            Mutability::Mut
+          } else {
+            Mutability::Not
+          };
+          Some((*loan_places.get(*place)?, mutability))
+        })
+        .collect::<Option<_>>()?;
+      loans.insert(RegionVid::from_usize(*region), region_loans);
+    }
+
+    let location_domain = LocationDomain::new(body);
+    if location_domain.num_real_locations() != self.state.len() {
+      return None;
+    }
+
+    let row_places = self.row_places.to_places(tcx, def_id, body, true)?;
+    let num_locations = location_domain.size();
+    let state = self
+      .state
+      .iter()
+      .map(|matrices| {
+        let matrices = matrices
+          .iter()
+          .map(|rows| {
+            let mut matrix = FlowDomainMatrix::new(&location_domain);
+            for (place, deps) in rows {
+              let mut deps_set = LocationSet::new(&location_domain);
+              for index in deps {
+                if *index >= num_locations {
+                  return None;
+                }
+                deps_set.insert(LocationIndex::new(*index));
+              }
+              matrix.union_into_row(*row_places.get(*place)?, &deps_set);
+            }
+            Some(matrix)
+          })
+          .collect::<Option<Vec<_>>>()?;
+        D::from_matrices(matrices)
+      })
+      .collect::<Option<IndexVec<LocationIndex, _>>>()?;
+
+    let aliases = Aliases::from_loans(tcx, def_id, body, location_domain.clone(), loans);
+    let control_dependencies = ControlDependencies::build(body);
+    let analysis = FlowAnalysis::new(tcx, def_id, body, aliases, control_dependencies);
+    Some(AnalysisResults::from_raw(analysis, location_domain, state))
+  }
+
+  /// Checks that every place in the cache can be reconstructed exactly. This can
+  /// fail e.g. if a field's type contains regions that differ from those of its
+  /// parent's type.
+  fn is_faithful<'tcx, D: CachedDomain<'tcx>>(
+    &self,
+    tcx: TyCtxt<'tcx>,
+    def_id: DefId,
+    results: &FlowResults<'_, 'tcx, D>,
+  ) -> bool {
+    let body = results.analysis.body;
+    let loan_places = match self.loan_places.to_places(tcx, def_id, body, false) {
+      Some(places) => places.into_iter().collect::<HashSet<_>>(),
+      None => return false,
+    };
+    let loans_match = results
+      .analysis
+      .aliases
+      .loans()
+      .values()
+      .flatten()
+      .all(|(place, _)| loan_places.contains(place));
+
+    let row_places = match self.row_places.to_places(tcx, def_id, body, true) {
+      Some(places) => places.into_iter().collect::<HashSet<_>>(),
+      None => return false,
+    };
+    let location_domain = results.analysis.location_domain();
+    let rows_match = (0 .. location_domain.num_real_locations()).all(|index| {
+      let location = *location_domain.value(LocationIndex::new(index));
+      let matrices = results.state_at(location).matrices();
+      matrices
+        .into_iter()
+        .flat_map(|matrix| matrix.rows())
+        .all(|(place, _)| row_places.contains(&place))
+    });
+
+    loans_match && rows_match
+  }
+}
+
+/// Computes a key that changes whenever the body, the evaluation mode, the available
+/// exported summaries, or the annotations and globals of the body's callees change.
+fn cache_key<'tcx>(
+  tcx: TyCtxt<'tcx>,
+  body_with_facts: &CachedSimplifedBodyWithFacts<'tcx>,
+) -> String {
+  let mut hasher = StableHasher::new();
+  env!("CARGO_PKG_VERSION").hash(&mut hasher);
+  EVAL_MODE.copied().hash(&mut hasher);
+  summary::external_summary_stamps(tcx).hash(&mut hasher);
+
+  // Region variables can't be stably hashed, so we hash the body without regions
+  // along with the outlives-constraints between regions. The regions are numbered
+  // deterministically, so the same body with the same constraints will have the same
+  // regions.
+  let body = tcx.erase_regions(body_with_facts.simplified_body().clone());
+  let mut hcx = tcx.create_stable_hashing_context();
+  body.hash_stable(&mut hcx, &mut hasher);
+  body_with_facts.input_facts().subset_base.hash(&mut hasher);
+
+  // Calls are approximated from their signature, except for the annotations of callees
+  // and the globals that local callees access. The globals accessed by the body itself
+  // are part of its MIR.
+  let body = body_with_facts.simplified_body();
+  let def_id = body.source.def_id();
+  let track_globals = is_extension_active(|mode| mode.globals_mode == GlobalsMode::Track);
+  for data in body.basic_blocks() {
+    let kind = &data.terminator().kind;
+    if let TerminatorKind::Call { func, .. } = kind {
+      let callee = utils::resolve_callee(tcx, def_id, func);
+      let flows = callee.and_then(|(callee, _)| models::annotated_flows(tcx, callee));
+      flows.hash(&mut hasher);
+    }
+    if track_globals {
+      if let Some(callee) = local_callee(tcx, kind) {
+        let callee_globals = globals::accessed_by_function(tcx, callee)
+          .into_iter()
+          .map(|global| tcx.def_path_hash(global))
+          .collect::<Vec<_>>();
+        callee_globals.hash(&mut hasher);
+      }
+    }
+  }
+
+  let fingerprint: Fingerprint = hasher.finish();
+  fingerprint.to_hex()
+}
+
+fn cache_path(cache_dir: &Path, tcx: TyCtxt<'_>, def_id: DefId, name: &str) -> PathBuf {
+  let def_path_hash = tcx.def_path_hash(def_id);
+  cache_dir.join(format!("{}.{name}.json", def_path_hash.0.to_hex()))
+}
+
+fn load(path: &Path, key: &str) -> Result<CachedResults> {
+  let contents = fs::read_to_string(path)?;
+  let cached: CachedResults = serde_json::from_str(&contents)?;
+  if cached.key != key {
+    bail!("Stale cache entry");
+  }
+  Ok(cached)
+}
+
+fn store(path: &Path, cached: &CachedResults) -> Result<()> {
+  if let Some(dir) = path.parent() {
+    fs::create_dir_all(dir)?;
+  }
+  fs::write(path, serde_json::to_string(cached)?)?;
+  Ok(())
+}
+
+/// Loads the flow results for `body_id` from the cache if available, otherwise
+/// calls `compute` and saves its results to the cache.
+crate fn load_or_compute<'a, 'tcx, D: CachedDomain<'tcx>>(
+  tcx: TyCtxt<'tcx>,
+  body_id: BodyId,
+  body_with_facts: &'a CachedSimplifedBodyWithFacts<'tcx>,
+  compute: impl FnOnce() -> FlowResults<'a, 'tcx, D>,
+) -> FlowResults<'a, 'tcx, D> {
+  let cache_dir = match CACHE_DIR.cloned() {
+    Some(cache_dir) => cache_dir,
+    None => return compute(),
+  };
+
+  let context_sensitive =
+    is_extension_active(|mode| mode.context_mode != ContextMode::SigOnly);
+  let beyond_loans = is_extension_active(|mode| {
+    matches!(
+      mode.pointer_mode,
+      PointerMode::FlowSensitive | PointerMode::PointsTo
+    )
+  });
+  let nested = BODY_STACK.with(|body_stack| !body_stack.borrow().is_empty());
+  if context_sensitive || beyond_loans || nested {
+    return compute();
+  }
+
+  let def_id = tcx.hir().body_owner_def_id(body_id).to_def_id();
+  let body = body_with_facts.simplified_body();
+  let path = cache_path(&cache_dir, tcx, def_id, D::NAME);
+  let key = cache_key(tcx, body_with_facts);
+
+  match load(&path, &key) {
+    Ok(cached) => {
+      block_timer!("Load cached flow");
+      if let Some(results) = cached.to_results(tcx, def_id, body) {
+        return results;
+      }
+      debug!("Cached flow for {def_id:?} does not match body");
+    }
+    Err(err) => debug!("No cached flow for {def_id:?}: {err}"),
+  }
+
+  let results = compute();
+
+  let cached = CachedResults::from_results(key, &results);
+  if cached.is_faithful(tcx, def_id, &results) {
+    if let Err(err) = store(&path, &cached) {
+      warn!("Failed to cache flow for {def_id:?}: {err}");
+    }
+  } else {
+    debug!("Flow for {def_id:?} cannot be cached");
+  }
+
+  results
+}
+
+#[cfg(test)]
+mod test {
+  use fluid_let::fluid_set;
+
+  use super::*;
+  use crate::{
+    extensions::EvalMode, infoflow, mir::utils::BodyExt, source_map::FunctionIdentifier,
+    test_utils,
+  };
+
+  fn roundtrip<'tcx, D: CachedDomain<'tcx>>(
+    tcx: TyCtxt<'tcx>,
+    def_id: DefId,
+    results: &FlowResults<'_, 'tcx, D>,
+  ) {
+    let body = results.analysis.body;
+    let cached = CachedResults::from_results(String::new(), results);
+    assert!(cached.is_faithful(tcx, def_id, results));
+
+    let cached: CachedResults =
+      serde_json::from_str(&serde_json::to_string(&cached).unwrap()).unwrap();
+    let loaded: FlowResults<D> = cached.to_results(tcx, def_id, body).unwrap();
+
+    assert_eq!(
+      results.analysis.aliases.loans(),
+      loaded.analysis.aliases.loans()
+    );
+    for location in body.all_locations() {
+      assert!(results.state_at(location) == loaded.state_at(location));
+    }
+  }
+
+  #[test]
+  fn test_cache_roundtrip() {
+    let input = r#"
+    fn main() {
+      let mut x = (1, 2);
+      let y = &mut x.0;
+      *y += 1;
+      let mut v = vec![x.1];
+      if *y > 0 { v.push(x.1); }
+    }
+    "#;
+    test_utils::compile_body(input, |tcx, body_id, body_with_facts| {
+      let def_id = tcx.hir().body_owner_def_id(body_id).to_def_id();
+      let results = infoflow::compute_flow(tcx, body_id, body_with_facts);
+      roundtrip(tcx, def_id, &results);
+
+      let results = infoflow::compute_flow_with_kinds(tcx, body_id, body_with_facts);
+      roundtrip(tcx, def_id, &results);
+    });
+  }
+
+  #[test]
+  fn test_cache_key_callees() {
+    let key = |callee: &str| {
+      let input = format!(
+        "#![feature(register_tool)]\n#![register_tool(flowistry_tool)]\n\
+         fn main() {{ f(); }}\n{callee}"
+      );
+      let main = FunctionIdentifier::Qpath("main".to_string());
+      let mut key = String::new();
+      test_utils::compile_body_with_range(
+        input,
+        main,
+        models::ANNOTATION_RUSTC_ARGS,
+        |tcx, _, body_with_facts| {
+          let mode = EvalMode {
+            globals_mode: GlobalsMode::Track,
+            ..Default::default()
+          };
+          fluid_set!(EVAL_MODE, &mode);
+          key = cache_key(tcx, body_with_facts);
+        },
+      );
+      key
+    };
+
+    let plain = key("fn f() {}");
+    assert_eq!(plain, key("fn f() { let x = 1; }"));
+    assert_ne!(plain, key("#[flowistry_tool::flows()] fn f() {}"));
+    assert_ne!(
+      plain,
+      key("static mut X: i32 = 0; fn f() { unsafe { X = 1; } }")
+    );
+    assert_ne!(
+      plain,
+      key("static mut X: i32 = 0; fn f() { g(); } fn g() { unsafe { X = 1; } }")
+    );
+  }
+}
@@ -6,7 +6,10 @@ use rustc_middle::mir::{visit::Visitor, *};
 use rustc_mir_dataflow::JoinSemiLattice;
 use rustc_span::Span;
 
-use super::{mutation::ModularMutationVisitor, FlowDomain, FlowResults};
+use super::{
+  mutation::ModularMutationVisitor, DependencyKind, FlowDomain, FlowResults,
+  KindedFlowDomain,
+};
 use crate::{
   block_timer,
   indexed::impls::LocationSet,
@@ -192,36 +195,121 @@ pub fn compute_dependencies<'tcx, D: FlowDomain<'tcx> + JoinSemiLattice>(
   outputs.into_inner()
 }
 
+/// Computes the backward slice of each of `all_targets` like [`compute_dependencies`],
+/// keeping only the dependencies of the given `kind`.
+pub fn compute_dependencies_of_kind<'tcx>(
+  results: &FlowResults<'_, 'tcx, KindedFlowDomain<'tcx>>,
+  all_targets: Vec<Vec<(Place<'tcx>, Location)>>,
+  kind: DependencyKind,
+) -> Vec<LocationSet> {
+  block_timer!("compute_dependencies_of_kind");
+  let aliases = &results.analysis.aliases;
+  let location_domain = results.analysis.location_domain();
+
+  all_targets
+    .into_iter()
+    .map(|targets| {
+      let mut outputs = LocationSet::new(location_domain);
+      for (place, location) in targets {
+        // An argument only depends on itself, which is a data dependency
+        if location_domain.location_to_local(location).is_some() {
+          if kind == DependencyKind::Data {
+            outputs.insert(location);
+          }
+          continue;
+        }
+
+        let state = results.state_at(location);
+        for value in aliases.reachable_values(place, Mutability::Not) {
+          outputs.union(&state.row_of_kind(aliases.normalize(*value), kind));
+        }
+      }
+      outputs
+    })
+    .collect()
+}
+
+/// Computes the chop between `sources` and `sinks`, i.e. the locations on some path
+/// of dependencies from a source to a sink.
+///
+/// The chop is the intersection of the forward slice of the sources with the backward
+/// slice of the sinks.
+pub fn compute_chop<'tcx, D: FlowDomain<'tcx> + JoinSemiLattice>(
+  results: &FlowResults<'_, 'tcx, D>,
+  sources: Vec<(Place<'tcx>, Location)>,
+  sinks: Vec<(Place<'tcx>, Location)>,
+) -> LocationSet {
+  block_timer!("compute_chop");
+  let forward = compute_dependencies(results, vec![sources], Direction::Forward);
+  let mut backward = compute_dependencies(results, vec![sinks], Direction::Backward);
+
+  let mut chop = backward.remove(0);
+  chop.intersect(&forward[0]);
+  debug!("chop={chop:?}");
+  chop
+}
+
+fn location_set_to_spans<'tcx, D: FlowDomain<'tcx> + JoinSemiLattice>(
+  results: &FlowResults<'_, 'tcx, D>,
+  deps: LocationSet,
+  spanner: &Spanner,
+) -> Vec<Span> {
+  let body = results.analysis.body;
+  let location_domain = results.analysis.location_domain();
+
+  let location_spans = deps
+    .iter()
+    .flat_map(|location| {
+      spanner.location_to_spans(
+        *location,
+        location_domain,
+        body,
+        EnclosingHirSpans::OuterOnly,
+      )
+    })
+    .collect::<Vec<_>>();
+
+  let merged_spans = Span::merge_overlaps(location_spans);
+  trace!("Spans: {merged_spans:?}");
+  merged_spans
+}
+
 pub fn compute_dependency_spans<'tcx, D: FlowDomain<'tcx> + JoinSemiLattice>(
   results: &FlowResults<'_, 'tcx, D>,
   targets: Vec<Vec<(Place<'tcx>, Location)>>,
   direction: Direction,
   spanner: &Spanner,
 ) -> Vec<Vec<Span>> {
-  let body = results.analysis.body;
-
-  let location_domain = results.analysis.location_domain();
   let all_deps = compute_dependencies(results, targets, direction);
   debug!("all_deps={all_deps:?}");
 
   all_deps
     .into_iter()
-    .map(|deps| {
-      let location_spans = deps
-        .iter()
-        .flat_map(|location| {
-          spanner.location_to_spans(
-            *location,
-            location_domain,
-            body,
-            EnclosingHirSpans::OuterOnly,
-          )
-        })
-        .collect::<Vec<_>>();
-
-      let merged_spans = Span::merge_overlaps(location_spans);
-      trace!("Spans: {merged_spans:?}");
-      merged_spans
-    })
+    .map(|deps| location_set_to_spans(results, deps, spanner))
+    .collect::<Vec<_>>()
+}
+
+pub fn compute_dependency_kind_spans<'tcx>(
+  results: &FlowResults<'_, 'tcx, KindedFlowDomain<'tcx>>,
+  targets: Vec<Vec<(Place<'tcx>, Location)>>,
+  kind: DependencyKind,
+  spanner: &Spanner,
+) -> Vec<Vec<Span>> {
+  let all_deps = compute_dependencies_of_kind(results, targets, kind);
+  debug!("all_deps of kind {kind:?}={all_deps:?}");
+
+  all_deps
+    .into_iter()
+    .map(|deps| location_set_to_spans(results, deps, spanner))
     .collect::<Vec<_>>()
 }
+
+pub fn compute_chop_spans<'tcx, D: FlowDomain<'tcx> + JoinSemiLattice>(
+  results: &FlowResults<'_, 'tcx, D>,
+  sources: Vec<(Place<'tcx>, Location)>,
+  sinks: Vec<(Place<'tcx>, Location)>,
+  spanner: &Spanner,
+) -> Vec<Span> {
+  let chop = compute_chop(results, sources, sinks);
+  location_set_to_spans(results, chop, spanner)
+}
@@ -0,0 +1,404 @@
+//! Executable slices, i.e. backward slices emitted as compilable source code.
+//!
+//! The slice is computed as usual, and then every statement of the sliced function
+//! that does not overlap the slice is removed from the source. The control structure
+//! of the function is preserved: a statement containing part of the slice (e.g. an
+//! `if` or a loop) is kept, and only its nested statements are pruned. Tail
+//! expressions, items and `return`/`break`/`continue` statements are always kept.
+//!
+//! The slice of the return value is included when the function returns a value, since
+//! the kept `return`s and tail expression must still produce one. For the same reason,
+//! the slice includes the dependencies of the operand of every kept `return` or
+//! `break`, which may in turn keep more statements.
+//!
+//! A removed `let` statement can still declare a variable that is used by the rest of
+//! the slice, e.g. if its initial value is always overwritten. In that case the
+//! declaration is kept without its initializer.
+
+use std::ops::Range as ByteRange;
+
+use anyhow::{Context, Result};
+use log::debug;
+use rustc_data_structures::fx::FxHashSet as HashSet;
+use rustc_hir::{
+  self as hir,
+  def::Res,
+  intravisit::{self, Visitor as HirVisitor},
+  BodyId, ExprKind, HirId, QPath, StmtKind,
+};
+use rustc_middle::{hir::nested_filter::OnlyBodies, mir::*, ty::TyCtxt};
+use rustc_mir_dataflow::JoinSemiLattice;
+use rustc_span::{source_map::SourceMap, Span};
+
+use super::{
+  dependencies::{compute_dependency_spans, Direction},
+  FlowDomain, FlowResults,
+};
+use crate::{
+  block_timer,
+  mir::utils::{BodyExt, SpanExt},
+  source_map::Spanner,
+};
+
+struct StatementPruner<'tcx> {
+  tcx: TyCtxt<'tcx>,
+  item_span: Span,
+  slice: Vec<Span>,
+  // False within closures and macro expansions, whose statements are never removed
+  prune: bool,
+  removed: Vec<&'tcx hir::Stmt<'tcx>>,
+  used_locals: HashSet<HirId>,
+  // Spans of the operands of the kept `return`s and `break`s
+  operands: Vec<Span>,
+}
+
+impl<'tcx> StatementPruner<'tcx> {
+  fn keep(&self, stmt: &hir::Stmt) -> bool {
+    let expr = match stmt.kind {
+      StmtKind::Item(_) => return true,
+      StmtKind::Expr(expr) | StmtKind::Semi(expr) => Some(expr),
+      StmtKind::Local(_) => None,
+    };
+    if let Some(expr) = expr {
+      if matches!(
+        expr.kind,
+        ExprKind::Ret(_) | ExprKind::Break(..) | ExprKind::Continue(_)
+      ) {
+        return true;
+      }
+    }
+
+    match stmt.span.as_local(self.item_span) {
+      Some(span) => self
+        .slice
+        .iter()
+        .any(|slice_span| slice_span.overlaps(span)),
+      None => true,
+    }
+  }
+
+  fn visit_unpruned(&mut self, f: impl FnOnce(&mut Self)) {
+    let prune = self.prune;
+    self.prune = false;
+    f(self);
+    self.prune = prune;
+  }
+}
+
+impl<'tcx> HirVisitor<'tcx> for StatementPruner<'tcx> {
+  type NestedFilter = OnlyBodies;
+
+  fn nested_visit_map(&mut self) -> Self::Map {
+    self.tcx.hir()
+  }
+
+  fn visit_stmt(&mut self, stmt: &'tcx hir::Stmt<'tcx>) {
+    if !self.prune {
+      intravisit::walk_stmt(self, stmt);
+    } else if !self.keep(stmt) {
+      self.removed.push(stmt);
+    } else if stmt.span.from_expansion() {
+      self.visit_unpruned(|this| intravisit::walk_stmt(this, stmt));
+    } else {
+      intravisit::walk_stmt(self, stmt);
+    }
+  }
+
+  fn visit_expr(&mut self, expr: &'tcx hir::Expr<'tcx>) {
+    match expr.kind {
+      ExprKind::Path(QPath::Resolved(_, path)) => {
+        if let Res::Local(id) = path.res {
+          self.used_locals.insert(id);
+        }
+      }
+      ExprKind::Ret(Some(operand)) | ExprKind::Break(_, Some(operand)) if self.prune => {
+        if let Some(span) = operand.span.as_local(self.item_span) {
+          self.operands.push(span);
+        }
+      }
+      ExprKind::Closure(..) => {
+        self.visit_unpruned(|this| intravisit::walk_expr(this, expr));
+        return;
+      }
+      _ => {}
+    }
+    intravisit::walk_expr(self, expr);
+  }
+}
+
+/// Returns the edit to the source for a statement outside the slice: its removal,
+/// or its replacement by a declaration of the variables it binds if they are used.
+fn statement_edit(
+  stmt: &hir::Stmt,
+  used_locals: &HashSet<HirId>,
+  item_span: Span,
+  source_map: &SourceMap,
+) -> Option<(Span, String)> {
+  let span = stmt.span.as_local(item_span)?;
+  let replacement = match stmt.kind {
+    StmtKind::Local(local) if local.init.is_some() => {
+      let mut used = false;
+      local
+        .pat
+        .each_binding(|_, id, _, _| used |= used_locals.contains(&id));
+      if used {
+        let pat = source_map.span_to_snippet(local.pat.span).ok()?;
+        let ty = match local.ty {
+          Some(ty) => format!(": {}", source_map.span_to_snippet(ty.span).ok()?),
+          None => String::new(),
+        };
+        format!("let {pat}{ty};")
+      } else {
+        String::new()
+      }
+    }
+    _ => String::new(),
+  };
+  Some((span, replacement))
+}
+
+/// Applies `edits` to `src`, which must not overlap. A removal that leaves its lines
+/// empty also removes those lines.
+fn apply_edits(src: &str, mut edits: Vec<(ByteRange<usize>, String)>) -> String {
+  edits.sort_by_key(|(range, _)| range.start);
+
+  let bytes = src.as_bytes();
+  let is_blank = |b: u8| b == b' ' || b == b'\t';
+  let mut output = String::new();
+  let mut last = 0;
+  for (mut range, replacement) in edits {
+    debug_assert!(
+      range.start >= last,
+      "Edit {range:?} overlaps the previous edit ending at {last}"
+    );
+
+    if replacement.is_empty() {
+      // A statement from a macro call may not include its trailing semicolon
+      let mut end = range.end;
+      while end < bytes.len() && is_blank(bytes[end]) {
+        end += 1;
+      }
+      if end < bytes.len() && bytes[end] == b';' {
+        range.end = end + 1;
+      }
+
+      let mut start = range.start;
+      while start > last && is_blank(bytes[start - 1]) {
+        start -= 1;
+      }
+      let mut end = range.end;
+      while end < bytes.len() && is_blank(bytes[end]) {
+        end += 1;
+      }
+      let line_start = start == 0 || bytes[start - 1] == b'\n';
+      let line_end = end == bytes.len() || bytes[end] == b'\n';
+      if line_start && line_end {
+        range = start .. (end + 1).min(bytes.len());
+      }
+    }
+
+    output.push_str(&src[last .. range.start]);
+    output.push_str(&replacement);
+    last = range.end;
+  }
+  output.push_str(&src[last ..]);
+  output
+}
+
+/// Computes the backward slice of `targets`, and returns the source file containing
+/// the body with every statement outside the slice removed.
+///
+/// See the [module-level documentation](self) for how the body is rewritten.
+pub fn compute_executable_slice<'tcx, D: FlowDomain<'tcx> + JoinSemiLattice>(
+  results: &FlowResults<'_, 'tcx, D>,
+  body_id: BodyId,
+  mut targets: Vec<(Place<'tcx>, Location)>,
+  spanner: &Spanner<'_, 'tcx>,
+) -> Result<String> {
+  block_timer!("compute_executable_slice");
+  let tcx = results.analysis.tcx;
+  let body = results.analysis.body;
+  let source_map = tcx.sess.source_map();
+
+  if !body.return_ty().is_unit() {
+    targets.extend(
+      body
+        .all_returns()
+        .map(|location| (Place::return_place(), location)),
+    );
+  }
+
+  // Add the dependencies of the operands of kept `return`s and `break`s to the slice
+  // until it no longer keeps new ones. Each round only slices on the new targets,
+  // since the slice of the previous targets is already known.
+  let mut new_targets = targets.clone();
+  let mut targets = targets.into_iter().collect::<HashSet<_>>();
+  let mut slice = Vec::new();
+  let pruner = loop {
+    let new_slice =
+      compute_dependency_spans(results, vec![new_targets], Direction::Backward, spanner)
+        .remove(0);
+    debug!("Slice: {new_slice:?}");
+    slice.extend(new_slice);
+
+    let mut pruner = StatementPruner {
+      tcx,
+      item_span: spanner.item_span,
+      slice,
+      prune: true,
+      removed: Vec::new(),
+      used_locals: HashSet::default(),
+      operands: Vec::new(),
+    };
+    pruner.visit_body(tcx.hir().body(body_id));
+
+    new_targets = pruner
+      .operands
+      .iter()
+      .flat_map(|span| spanner.span_to_targets(*span).concat())
+      .filter(|target| targets.insert(*target))
+      .collect::<Vec<_>>();
+    if new_targets.is_empty() {
+      break pruner;
+    }
+    debug!("Operand targets: {new_targets:?}");
+    slice = pruner.slice;
+  };
+  debug!("Removed statements: {:?}", pruner.removed);
+
+  let file = source_map.lookup_source_file(spanner.item_span.lo());
+  let edits = pruner
+    .removed
+    .iter()
+    .filter_map(|stmt| {
+      statement_edit(stmt, &pruner.used_locals, spanner.item_span, source_map)
+    })
+    .map(|(span, replacement)| {
+      let lo = (span.lo() - file.start_pos).0 as usize;
+      let hi = (span.hi() - file.start_pos).0 as usize;
+      (lo .. hi, replacement)
+    })
+    .collect::<Vec<_>>();
+
+  source_map.ensure_source_file_source_present(file.clone());
+  let external = file.external_src.borrow();
+  let src = file
+    .src
+    .as_deref()
+    .or_else(|| external.get_source().map(|src| &**src))
+    .with_context(|| format!("Could not load source for file: {:?}", file.name))?;
+
+  Ok(apply_edits(src, edits))
+}
+
+#[cfg(test)]
+mod test {
+  use rustc_middle::ty::TyCtxt;
+  use test_log::test;
+
+  use super::*;
+  use crate::{infoflow, test_utils};
+
+  fn executable_slice(input: &str, target: &str) -> String {
+    let target_start = input.find(target).unwrap();
+    let target_range = (target_start, target_start + target.len());
+    let mut output = String::new();
+    test_utils::compile_body(input, |tcx: TyCtxt<'_>, body_id, body_with_facts| {
+      let target_span = test_utils::make_span(target_range);
+      let results = &infoflow::compute_flow(tcx, body_id, body_with_facts);
+      let spanner = Spanner::new(tcx, body_id, body_with_facts.simplified_body());
+      let targets = spanner.span_to_targets(target_span).concat();
+      output = compute_executable_slice(results, body_id, targets, &spanner).unwrap();
+    });
+    output
+  }
+
+  #[test]
+  fn test_executable_slice() {
+    let input = r#"
+fn main() {
+  let x = 1;
+  let y = 2;
+  let mut z = 0;
+  z = x;
+  if y > 0 {
+    z += 1;
+    println!("{}", y);
+  }
+  for i in 0 .. 3 {
+    println!("{}", i);
+  }
+  let w = z;
+}"#;
+    let expected = r#"
+fn main() {
+  let x = 1;
+  let y = 2;
+  let mut z;
+  z = x;
+  if y > 0 {
+    z += 1;
+  }
+  let w = z;
+}"#;
+    assert_eq!(executable_slice(input, "w"), expected);
+  }
+
+  #[test]
+  fn test_executable_slice_return() {
+    let input = r#"
+fn main() -> i32 {
+  let x = 1;
+  let y = 2;
+  let z = 3;
+  if x > 0 {
+    return y;
+  }
+  let w = x + 1;
+  0
+}"#;
+    let expected = r#"
+fn main() -> i32 {
+  let x = 1;
+  let y = 2;
+  if x > 0 {
+    return y;
+  }
+  let w = x + 1;
+  0
+}"#;
+    assert_eq!(executable_slice(input, "w"), expected);
+  }
+
+  #[test]
+  fn test_executable_slice_break_operand() {
+    let input = r#"
+fn main() {
+  let x = 1;
+  let y = 2;
+  let u = 3;
+  let mut z = 0;
+  let v = loop {
+    z += x;
+    if z > 5 {
+      break y;
+    }
+  };
+  let w = z;
+}"#;
+    let expected = r#"
+fn main() {
+  let x = 1;
+  let y = 2;
+  let mut z = 0;
+  let v = loop {
+    z += x;
+    if z > 5 {
+      break y;
+    }
+  };
+  let w = z;
+}"#;
+    assert_eq!(executable_slice(input, "w"), expected);
+  }
+}
@@ -0,0 +1,494 @@
+//! Explanations of why a location is in the slice of a target.
+//!
+//! The information flow analysis only computes the transitive dependencies of each
+//! place, so it cannot say how a location influences a target. To find out, the
+//! analysis is run again to compute the writes that reach each location instead
+//! (see [`ReachingWrites`]).
+//! Each location then directly depends on the reaching writes of the places it reads,
+//! and on the branches it is control-dependent on. A shortest chain of such direct
+//! dependencies from the target back to a location in its slice is the explanation.
+
+use std::{borrow::Cow, collections::VecDeque, fmt, rc::Rc};
+
+use log::debug;
+use rustc_data_structures::fx::FxHashMap as HashMap;
+use rustc_hir::BodyId;
+use rustc_middle::{
+  mir::{visit::Visitor, *},
+  ty::TyCtxt,
+};
+use rustc_mir_dataflow::{AnalysisDomain, JoinSemiLattice};
+use serde::Serialize;
+
+use super::{
+  dependencies::{compute_dependencies, Direction},
+  mutation::{ModularMutationVisitor, MutationStatus},
+  FlowDomain, FlowDomainMatrix, FlowResults, KindSets,
+};
+use crate::{
+  block_timer,
+  extensions::{is_extension_active, ControlMode, SliceMode},
+  indexed::{
+    impls::{LocationDomain, LocationSet},
+    IndexSet, RefSet, ToSet,
+  },
+  mir::{
+    borrowck_facts::CachedSimplifedBodyWithFacts,
+    utils::{OperandExt, PlaceExt},
+  },
+};
+
+/// Why a location influences the next location in a chain of dependencies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum DependencyReason {
+  /// The location writes a place that is read by the next location.
+  Data,
+  /// The location writes a place that is read by the next location through an
+  /// alias, e.g. `y` is written and `*x` is read where `x = &mut y`.
+  Alias,
+  /// The location writes a reference that is dereferenced by the next location.
+  Provenance,
+  /// The location is a branch that decides whether the next location executes.
+  Control,
+}
+
+/// A location in a chain of dependencies, along with why it influences the previous
+/// location in the chain (or the target, for the first step of the chain).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DependencyStep {
+  pub location: Location,
+  pub reason: DependencyReason,
+}
+
+/// Why a dependency could not be explained by [`explain_dependency`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExplainError {
+  /// None of the sources are in the slice of any of the targets.
+  NotInSlice,
+  /// A source is in the slice of a target, but no chain of direct dependencies
+  /// within the slice leads from the target to the source.
+  NoChain,
+}
+
+impl fmt::Display for ExplainError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      ExplainError::NotInSlice => {
+        write!(f, "Selection is not in the slice of the target")
+      }
+      ExplainError::NoChain => write!(
+        f,
+        "Selection is in the slice of the target, but how could not be explained"
+      ),
+    }
+  }
+}
+
+impl std::error::Error for ExplainError {}
+
+/// Maps each place to the locations whose writes to the place reach the current
+/// location, i.e. a non-transitive version of
+/// [`TransitiveFlowDomain`](super::TransitiveFlowDomain).
+///
+/// Running [`FlowAnalysis`](super::FlowAnalysis) with this domain computes the writes
+/// of calls, summaries and globals the same way as the dependencies in the slice.
+#[derive(PartialEq, Eq, Clone)]
+struct ReachingWrites<'tcx>(FlowDomainMatrix<'tcx>);
+
+impl<'tcx> FlowDomain<'tcx> for ReachingWrites<'tcx> {
+  fn matrix(&self) -> &FlowDomainMatrix<'tcx> {
+    &self.0
+  }
+  fn matrix_mut(&mut self) -> &mut FlowDomainMatrix<'tcx> {
+    &mut self.0
+  }
+  fn row<'a>(&'a self, row: Place<'tcx>) -> IndexSet<Location, RefSet<'a, Location>> {
+    self.0.row_set(row)
+  }
+  fn union_after<S: ToSet<Location>>(
+    &mut self,
+    row: Place<'tcx>,
+    _from: &IndexSet<Location, S>,
+    _kinds: Option<&KindSets>,
+    at: Location,
+  ) -> bool {
+    self.0.insert(row, at)
+  }
+  fn from_location_domain(dom: &Rc<LocationDomain>) -> Self {
+    ReachingWrites(FlowDomainMatrix::new(dom))
+  }
+  fn include(&mut self, row: Place<'tcx>, at: Location) -> bool {
+    self.0.insert(row, at)
+  }
+}
+
+impl JoinSemiLattice for ReachingWrites<'_> {
+  fn join(&mut self, other: &Self) -> bool {
+    self.0.join(&other.0)
+  }
+}
+
+struct Explainer<'a, 'tcx> {
+  writes: FlowResults<'a, 'tcx, ReachingWrites<'tcx>>,
+}
+
+impl<'a, 'tcx> Explainer<'a, 'tcx> {
+  /// Returns the writes that reach `location` before its own effect is applied.
+  fn state_before(&self, location: Location) -> Cow<'_, ReachingWrites<'tcx>> {
+    if location.statement_index > 0 {
+      return Cow::Borrowed(self.writes.state_at(Location {
+        statement_index: location.statement_index - 1,
+        ..location
+      }));
+    }
+
+    let analysis = &self.writes.analysis;
+    let body = analysis.body;
+    let mut state = analysis.bottom_value(body);
+    if location == Location::START {
+      analysis.initialize_start_block(body, &mut state);
+    }
+    for pred in body.predecessors()[location.block].iter() {
+      state.join(self.writes.state_at(body.terminator_loc(*pred)));
+    }
+    Cow::Owned(state)
+  }
+
+  /// Adds the writes in `state` that `place` depends on when it is read at `location`.
+  /// If `read_value` is false, then only the provenance of `place` is read.
+  fn place_deps(
+    &self,
+    state: &ReachingWrites<'tcx>,
+    place: Place<'tcx>,
+    read_value: bool,
+    location: Location,
+    deps: &mut Vec<(Location, DependencyReason)>,
+  ) {
+    let aliases = &self.writes.analysis.aliases;
+    let tcx = self.writes.analysis.tcx;
+    let mut add = |relevant: Place<'tcx>, reason: DependencyReason| {
+      let writes = state.row(aliases.normalize(relevant));
+      deps.extend(writes.iter().map(|write| (*write, reason)));
+    };
+
+    if read_value {
+      for relevant in aliases
+        .reachable_values_at(place, Mutability::Not, location)
+        .iter()
+      {
+        let reason = if relevant.local == place.local {
+          DependencyReason::Data
+        } else {
+          DependencyReason::Alias
+        };
+        add(*relevant, reason);
+      }
+    }
+
+    // A thin slice does not depend on the pointers used to reach a place
+    if is_extension_active(|mode| mode.slice_mode == SliceMode::Thin) {
+      return;
+    }
+    for (place_ref, _) in place.refs_in_projection() {
+      let pointer = Place::from_ref(place_ref, tcx);
+      for alias in aliases.aliases_at(pointer, location).iter() {
+        add(*alias, DependencyReason::Provenance);
+      }
+    }
+  }
+
+  /// Returns the locations that `location` directly depends on.
+  fn location_deps(&self, location: Location) -> Vec<(Location, DependencyReason)> {
+    let analysis = &self.writes.analysis;
+    let body = analysis.body;
+    if analysis
+      .location_domain()
+      .location_to_local(location)
+      .is_some()
+    {
+      return Vec::new();
+    }
+
+    // A mutated place is only read if its old value may be kept
+    let mut reads = Vec::new();
+    ModularMutationVisitor::new(
+      &analysis.aliases,
+      |mutated: Place<'tcx>,
+       inputs: &[(Place<'tcx>, Option<&'tcx [PlaceElem<'tcx>]>)],
+       _,
+       mutation_status| {
+        let keeps_value = matches!(mutation_status, MutationStatus::Possibly);
+        reads.push((mutated, keeps_value));
+        reads.extend(inputs.iter().map(|(input, _)| (*input, true)));
+      },
+    )
+    .visit_location(body, location);
+
+    if let Some(terminator) = body.stmt_at(location).right() {
+      let operand = match &terminator.kind {
+        TerminatorKind::SwitchInt { discr, .. } => Some(discr),
+        TerminatorKind::Assert { cond, .. } => Some(cond),
+        _ => None,
+      };
+      reads.extend(
+        operand
+          .and_then(|op| op.to_place())
+          .map(|place| (place, true)),
+      );
+    }
+
+    let state = self.state_before(location);
+    let mut deps = Vec::new();
+    for (place, read_value) in reads {
+      self.place_deps(&state, place, read_value, location, &mut deps);
+    }
+
+    let data_only =
+      is_extension_active(|mode| mode.control_mode == ControlMode::DataOnly);
+    if !data_only {
+      let controlled_by = analysis.control_dependencies.dependent_on(location.block);
+      deps.extend(
+        controlled_by
+          .into_iter()
+          .flat_map(|blocks| blocks.iter())
+          .map(|block| (body.terminator_loc(block), DependencyReason::Control)),
+      );
+    }
+
+    deps
+  }
+
+  /// Returns a shortest chain of direct dependencies within `slice` from `target`
+  /// to any of `sources`.
+  fn search(
+    &self,
+    target: (Place<'tcx>, Location),
+    slice: &LocationSet,
+    sources: &[Location],
+  ) -> Option<Vec<DependencyStep>> {
+    let (target_place, target_location) = target;
+    let location_domain = self.writes.analysis.location_domain();
+    let target_deps = if location_domain.location_to_local(target_location).is_some() {
+      vec![(target_location, DependencyReason::Data)]
+    } else {
+      let mut deps = Vec::new();
+      let state = self.writes.state_at(target_location);
+      self.place_deps(state, target_place, true, target_location, &mut deps);
+      deps
+    };
+
+    // Breadth-first search from the target, within the slice
+    let mut parents = HashMap::default();
+    let mut queue = VecDeque::new();
+    let mut visit = |deps: Vec<(Location, DependencyReason)>,
+                     parent: Option<Location>,
+                     queue: &mut VecDeque<Location>| {
+      for (location, reason) in deps {
+        if slice.contains(location) && !parents.contains_key(&location) {
+          parents.insert(location, (parent, reason));
+          queue.push_back(location);
+        }
+      }
+    };
+    visit(target_deps, None, &mut queue);
+
+    let source = loop {
+      let location = queue.pop_front()?;
+      if sources.contains(&location) {
+        break location;
+      }
+      visit(self.location_deps(location), Some(location), &mut queue);
+    };
+
+    let mut chain = Vec::new();
+    let mut location = source;
+    loop {
+      let (parent, reason) = parents[&location];
+      chain.push(DependencyStep { location, reason });
+      match parent {
+        Some(parent) => location = parent,
+        None => break,
+      }
+    }
+    chain.reverse();
+    Some(chain)
+  }
+}
+
+/// Explains why any of `sources` is in the backward slice of any of `targets`.
+///
+/// Returns a shortest chain of locations, starting from a location that a target
+/// directly depends on and ending at a source, where each location directly
+/// influences the previous one.
+pub fn explain_dependency<'tcx>(
+  tcx: TyCtxt<'tcx>,
+  body_id: BodyId,
+  body_with_facts: &CachedSimplifedBodyWithFacts<'tcx>,
+  targets: &[(Place<'tcx>, Location)],
+  sources: &[Location],
+) -> Result<Vec<DependencyStep>, ExplainError> {
+  block_timer!("explain_dependency");
+  let results = &super::compute_flow(tcx, body_id, body_with_facts);
+  let all_targets = targets.iter().map(|target| vec![*target]).collect();
+  let slices = compute_dependencies(results, all_targets, Direction::Backward);
+
+  let relevant = targets
+    .iter()
+    .zip(slices)
+    .filter(|(_, slice)| sources.iter().any(|source| slice.contains(*source)))
+    .collect::<Vec<_>>();
+  if relevant.is_empty() {
+    return Err(ExplainError::NotInSlice);
+  }
+
+  let explainer = Explainer {
+    writes: super::compute_flow_internal(tcx, body_id, body_with_facts),
+  };
+  let chain = relevant
+    .into_iter()
+    .filter_map(|(target, slice)| explainer.search(*target, &slice, sources))
+    .min_by_key(|chain| chain.len())
+    .ok_or(ExplainError::NoChain)?;
+
+  debug!("Explanation for {sources:?} in slice of {targets:?}: {chain:?}");
+  Ok(chain)
+}
+
+#[cfg(test)]
+mod test {
+  use fluid_let::fluid_set;
+  use rustc_middle::ty::TyCtxt;
+  use test_log::test;
+
+  use super::{DependencyReason::*, *};
+  use crate::{
+    extensions::{ContextMode, EvalMode, EVAL_MODE},
+    mir::utils::BodyExt,
+    test_utils,
+  };
+
+  fn explain_snippets(
+    input: &str,
+    target: &str,
+    source: &str,
+    mode: EvalMode,
+  ) -> Result<Vec<(String, DependencyReason)>, ExplainError> {
+    let (input, target, source) =
+      (input.to_string(), target.to_string(), source.to_string());
+    let mut output = Err(ExplainError::NotInSlice);
+    test_utils::compile_body(input, |tcx: TyCtxt<'_>, body_id, body_with_facts| {
+      fluid_set!(EVAL_MODE, &mode);
+      let body = body_with_facts.simplified_body();
+      let snippet = |location: Location| {
+        let span = body.source_info(location).span;
+        tcx.sess.source_map().span_to_snippet(span).unwrap()
+      };
+      let find = |s: &str| body.all_locations().find(|loc| snippet(*loc) == s).unwrap();
+
+      let target_location = find(&target);
+      let target_place = match &body.stmt_at(target_location).left().unwrap().kind {
+        StatementKind::Assign(box (place, _)) => *place,
+        _ => unreachable!(),
+      };
+      let chain = explain_dependency(
+        tcx,
+        body_id,
+        body_with_facts,
+        &[(target_place, target_location)],
+        &[find(&source)],
+      );
+      output = chain.map(|chain| {
+        chain
+          .into_iter()
+          .map(|step| (snippet(step.location), step.reason))
+          .collect()
+      });
+    });
+    output
+  }
+
+  fn steps<const N: usize>(
+    expected: [(&str, DependencyReason); N],
+  ) -> Result<Vec<(String, DependencyReason)>, ExplainError> {
+    Ok(expected.map(|(s, r)| (s.to_string(), r)).to_vec())
+  }
+
+  #[test]
+  fn test_explain() {
+    let input = r#"
+    fn main() {
+      let a = 1;
+      let b = a;
+      let c = b;
+      let d = &c;
+      let e = *d;
+      let f = if a > 0 { 2 } else { 3 };
+    }"#;
+    let mode = EvalMode::default();
+
+    let chain = explain_snippets(input, "*d", "1", mode);
+    let expected = [("*d", Data), ("b", Alias), ("a", Data), ("1", Data)];
+    assert_eq!(chain, steps(expected));
+
+    let chain = explain_snippets(input, "2", "1", mode);
+    let expected = [
+      ("2", Data),
+      ("a > 0", Control),
+      ("a > 0", Data),
+      ("a", Data),
+      ("1", Data),
+    ];
+    assert_eq!(chain, steps(expected));
+
+    let chain = explain_snippets(input, "*d", "2", mode);
+    assert_eq!(chain, Err(ExplainError::NotInSlice));
+  }
+
+  #[test]
+  fn test_explain_provenance() {
+    let input = r#"
+    fn main() {
+      let a = 1;
+      let b = 2;
+      let mut r = &a;
+      r = &b;
+      let c = *r;
+    }"#;
+
+    let chain = explain_snippets(input, "*r", "r = &b", EvalMode::default());
+    let expected = [("*r", Data), ("r = &b", Provenance)];
+    assert_eq!(chain, steps(expected));
+  }
+
+  #[test]
+  fn test_explain_call() {
+    let input = r#"
+    fn main() {
+      let a = 1;
+      let mut b = 0;
+      let mut d = 0;
+      set(&mut b, &mut d, a);
+      let c = b;
+      let e = d;
+    }
+
+    fn set(x: &mut i32, y: &mut i32, z: i32) { *x = z; }"#;
+    let mode = EvalMode {
+      context_mode: ContextMode::Recurse,
+      ..Default::default()
+    };
+
+    let chain = explain_snippets(input, "b", "1", mode);
+    let expected = [
+      ("b", Data),
+      ("set(&mut b, &mut d, a)", Data),
+      ("a", Data),
+      ("1", Data),
+    ];
+    assert_eq!(chain, steps(expected));
+
+    // The callee only writes to its first argument
+    let chain = explain_snippets(input, "d", "1", mode);
+    assert_eq!(chain, Err(ExplainError::NotInSlice));
+  }
+}
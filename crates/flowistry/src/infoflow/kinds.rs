@@ -0,0 +1,322 @@
+//! Information flow labeled with the kind of each dependency.
+//!
+//! A dependency of a place on a location is labeled with the kinds of the edges on
+//! the paths of direct dependencies from the location to the place:
+//! * [`DependencyKind::Data`] if some path only goes through direct reads of the
+//!   places written by each location,
+//! * [`DependencyKind::Control`] if some path goes through a control dependency,
+//! * [`DependencyKind::Alias`] if some path reads a place through an alias of the
+//!   place or through a pointer to it,
+//! * [`DependencyKind::Call`] if some path goes through a call whose effects are
+//!   approximated from the callee's signature.
+//!
+//! A dependency can have several labels, e.g. a place that reads `x` both directly and
+//! under a condition on `x` depends on `x` through a data and a control dependency.
+
+use std::{iter, rc::Rc};
+
+use rustc_middle::mir::{Location, Place};
+use rustc_mir_dataflow::JoinSemiLattice;
+use serde::Serialize;
+
+use super::{cache::CachedDomain, FlowDomain, FlowDomainMatrix};
+use crate::indexed::{
+  impls::{LocationDomain, LocationSet},
+  IndexSet, RefSet, ToSet,
+};
+
+/// The kind of a dependency, see the [module-level documentation](self).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum DependencyKind {
+  Data,
+  Control,
+  Alias,
+  Call,
+}
+
+impl DependencyKind {
+  /// All kinds, in the order that [`KindedFlowDomain::kinds_of`] returns them.
+  pub const ALL: [DependencyKind; 4] = [
+    DependencyKind::Data,
+    DependencyKind::Control,
+    DependencyKind::Alias,
+    DependencyKind::Call,
+  ];
+
+  /// The kinds of the edges that do not directly read a place, i.e. all kinds
+  /// but `Data`.
+  pub const INDIRECT: [DependencyKind; 3] = [
+    DependencyKind::Control,
+    DependencyKind::Alias,
+    DependencyKind::Call,
+  ];
+
+  fn index(self) -> usize {
+    match self {
+      DependencyKind::Data => 0,
+      DependencyKind::Control => 1,
+      DependencyKind::Alias => 2,
+      DependencyKind::Call => 3,
+    }
+  }
+}
+
+/// The dependencies of each place labeled with each kind.
+#[derive(PartialEq, Eq, Clone)]
+pub struct DependencyKinds<'tcx> {
+  matrices: [FlowDomainMatrix<'tcx>; 4],
+}
+
+impl<'tcx> DependencyKinds<'tcx> {
+  fn new(dom: &Rc<LocationDomain>) -> Self {
+    DependencyKinds {
+      matrices: [(); 4].map(|_| FlowDomainMatrix::new(dom)),
+    }
+  }
+
+  /// Returns the dependencies of `row` labeled with `kind`.
+  pub fn row(
+    &self,
+    row: Place<'tcx>,
+    kind: DependencyKind,
+  ) -> IndexSet<Location, RefSet<'_, Location>> {
+    self.matrices[kind.index()].row_set(row)
+  }
+
+  crate fn union_after(&mut self, row: Place<'tcx>, from: &KindSets) -> bool {
+    let mut changed = false;
+    for (matrix, set) in self.matrices.iter_mut().zip(&from.sets) {
+      changed |= matrix.union_into_row(row, set);
+    }
+    changed
+  }
+
+  crate fn clear_row(&mut self, row: Place<'tcx>) {
+    for matrix in self.matrices.iter_mut() {
+      matrix.clear_row(row);
+    }
+  }
+
+  /// Labels the dependency of `row` on the location `at` that writes it as `Data`.
+  crate fn include(&mut self, row: Place<'tcx>, at: Location) -> bool {
+    self.matrices[DependencyKind::Data.index()].insert(row, at)
+  }
+}
+
+/// A set of dependencies labeled with each kind, accumulated by
+/// [`FlowAnalysis::transfer_function`](super::FlowAnalysis::transfer_function) for
+/// a single mutation.
+pub struct KindSets {
+  sets: [LocationSet; 4],
+}
+
+impl KindSets {
+  crate fn new(dom: &Rc<LocationDomain>) -> Self {
+    KindSets {
+      sets: [(); 4].map(|_| LocationSet::new(dom)),
+    }
+  }
+
+  /// Adds the labeled dependencies of `row` in `kinds`, so that they keep their labels.
+  /// The `Data` dependencies of `row` are only kept if `row` is read directly, since
+  /// otherwise their paths go through an edge of another kind.
+  crate fn inherit<'tcx>(
+    &mut self,
+    kinds: &DependencyKinds<'tcx>,
+    row: Place<'tcx>,
+    direct: bool,
+  ) {
+    let labeled = DependencyKind::ALL.into_iter().zip(&kinds.matrices);
+    for (set, (kind, matrix)) in self.sets.iter_mut().zip(labeled) {
+      if direct || kind != DependencyKind::Data {
+        set.union(&matrix.row_set(row));
+      }
+    }
+  }
+
+  /// Labels the dependencies `deps` with `kind`.
+  crate fn label<S: ToSet<Location>>(
+    &mut self,
+    kind: DependencyKind,
+    deps: &IndexSet<Location, S>,
+  ) {
+    self.sets[kind.index()].union(deps);
+  }
+
+  crate fn insert(&mut self, kind: DependencyKind, location: Location) {
+    self.sets[kind.index()].insert(location);
+  }
+
+  crate fn union(&mut self, other: &KindSets) {
+    for (set, other) in self.sets.iter_mut().zip(&other.sets) {
+      set.union(other);
+    }
+  }
+}
+
+/// A transitive [`FlowDomain`] that also labels each dependency with its kinds.
+#[derive(PartialEq, Eq, Clone)]
+pub struct KindedFlowDomain<'tcx> {
+  matrix: FlowDomainMatrix<'tcx>,
+  kinds: DependencyKinds<'tcx>,
+}
+
+impl<'tcx> KindedFlowDomain<'tcx> {
+  /// Returns the dependencies of `place` of the given `kind`.
+  pub fn row_of_kind(&self, place: Place<'tcx>, kind: DependencyKind) -> LocationSet {
+    self.kinds.row(place, kind).to_owned()
+  }
+
+  /// Returns the kinds of the dependency of `place` on `location`, which is empty if
+  /// `place` does not depend on `location`.
+  pub fn kinds_of(&self, place: Place<'tcx>, location: Location) -> Vec<DependencyKind> {
+    DependencyKind::ALL
+      .into_iter()
+      .filter(|kind| self.kinds.row(place, *kind).contains(location))
+      .collect()
+  }
+}
+
+impl<'tcx> FlowDomain<'tcx> for KindedFlowDomain<'tcx> {
+  fn matrix(&self) -> &FlowDomainMatrix<'tcx> {
+    &self.matrix
+  }
+  fn matrix_mut(&mut self) -> &mut FlowDomainMatrix<'tcx> {
+    &mut self.matrix
+  }
+  fn row<'a>(&'a self, row: Place<'tcx>) -> IndexSet<Location, RefSet<'a, Location>> {
+    self.matrix.row_set(row)
+  }
+  fn union_after<S: ToSet<Location>>(
+    &mut self,
+    row: Place<'tcx>,
+    from: &IndexSet<Location, S>,
+    kinds: Option<&KindSets>,
+    _at: Location,
+  ) -> bool {
+    let mut changed = self.matrix.union_into_row(row, from);
+    if let Some(kinds) = kinds {
+      changed |= self.kinds.union_after(row, kinds);
+    }
+    changed
+  }
+  fn from_location_domain(dom: &Rc<LocationDomain>) -> Self {
+    KindedFlowDomain {
+      matrix: FlowDomainMatrix::new(dom),
+      kinds: DependencyKinds::new(dom),
+    }
+  }
+  fn include(&mut self, row: Place<'tcx>, at: Location) -> bool {
+    let changed = self.matrix.insert(row, at);
+    self.kinds.include(row, at) || changed
+  }
+  // Arguments are labeled as data, so that they start every path of direct reads
+  fn include_arg(&mut self, row: Place<'tcx>, at: Location) -> bool {
+    self.include(row, at)
+  }
+  fn clear_row(&mut self, row: Place<'tcx>) {
+    self.matrix.clear_row(row);
+    self.kinds.clear_row(row);
+  }
+  fn kinds(&self) -> Option<&DependencyKinds<'tcx>> {
+    Some(&self.kinds)
+  }
+}
+
+impl<'tcx> JoinSemiLattice for KindedFlowDomain<'tcx> {
+  fn join(&mut self, other: &Self) -> bool {
+    let mut changed = self.matrix.join(&other.matrix);
+    for (matrix, other) in self.kinds.matrices.iter_mut().zip(&other.kinds.matrices) {
+      changed |= matrix.join(other);
+    }
+    changed
+  }
+}
+
+impl<'tcx> CachedDomain<'tcx> for KindedFlowDomain<'tcx> {
+  const NAME: &'static str = "kinds";
+
+  fn matrices(&self) -> Vec<&FlowDomainMatrix<'tcx>> {
+    iter::once(&self.matrix)
+      .chain(&self.kinds.matrices)
+      .collect()
+  }
+
+  fn from_matrices(matrices: Vec<FlowDomainMatrix<'tcx>>) -> Option<Self> {
+    let [matrix, data, control, alias, call]: [_; 5] = matrices.try_into().ok()?;
+    Some(KindedFlowDomain {
+      matrix,
+      kinds: DependencyKinds {
+        matrices: [data, control, alias, call],
+      },
+    })
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use rustc_middle::{mir::*, ty::TyCtxt};
+  use test_log::test;
+
+  use super::DependencyKind::*;
+  use crate::{infoflow, mir::utils::BodyExt, test_utils};
+
+  #[test]
+  fn test_kinds() {
+    let input = r#"
+    fn main() {
+      let x = 1;
+      let mut y = 0;
+      if x > 0 {
+        y = 2;
+      }
+      let r = &y;
+      let z = *r;
+      let w = f(x);
+      let v = x;
+      let u = x + y;
+    }
+
+    fn f(a: i32) -> i32 { a }
+    "#;
+    test_utils::compile_body(input, |tcx: TyCtxt<'_>, body_id, body_with_facts| {
+      let results = infoflow::compute_flow_with_kinds(tcx, body_id, body_with_facts);
+      let body = results.analysis.body;
+      let location = |s: &str| {
+        body
+          .all_locations()
+          .find(|location| {
+            let span = body.source_info(*location).span;
+            tcx.sess.source_map().span_to_snippet(span).unwrap() == s
+          })
+          .unwrap()
+      };
+      let var = |s: &str| {
+        let info = body
+          .var_debug_info
+          .iter()
+          .find(|info| info.name.as_str() == s);
+        match info.unwrap().value {
+          VarDebugInfoContents::Place(place) => place,
+          _ => unreachable!(),
+        }
+      };
+
+      let state = results.state_at(body.all_returns().next().unwrap());
+      let (x_def, y_def) = (location("1"), location("y = 2"));
+      assert_eq!(state.kinds_of(var("v"), x_def), vec![Data]);
+      assert_eq!(state.kinds_of(var("w"), x_def), vec![Call]);
+      assert_eq!(state.kinds_of(var("z"), x_def), vec![Control, Alias]);
+      assert_eq!(state.kinds_of(var("z"), y_def), vec![Alias]);
+      assert_eq!(state.kinds_of(var("v"), y_def), vec![]);
+
+      // u reads x directly, and through y which is written under a condition on x
+      assert_eq!(state.kinds_of(var("u"), x_def), vec![Data, Control]);
+      assert!(state.row_of_kind(var("u"), Data).contains(x_def));
+
+      assert!(state.row_of_kind(var("v"), Data).contains(x_def));
+      assert!(!state.row_of_kind(var("z"), Data).contains(x_def));
+      assert!(state.row_of_kind(var("z"), Control).contains(x_def));
+    });
+  }
+}
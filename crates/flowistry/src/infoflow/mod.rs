@@ -3,9 +3,8 @@
 use std::cell::RefCell;
 
 use log::debug;
-use rustc_borrowck::consumers::BodyWithBorrowckFacts;
 use rustc_hir::BodyId;
-use rustc_middle::ty::TyCtxt;
+use rustc_middle::ty::{subst::SubstsRef, TyCtxt};
 use rustc_mir_dataflow::JoinSemiLattice;
 
 pub use self::{
@@ -13,7 +12,13 @@ pub use self::{
     FlowAnalysis, FlowDomain, FlowDomainMatrix, NonTransitiveFlowDomain,
     TransitiveFlowDomain,
   },
-  dependencies::{compute_dependencies, compute_dependency_spans, Direction},
+  dependencies::{
+    compute_chop, compute_chop_spans, compute_dependencies, compute_dependencies_of_kind,
+    compute_dependency_kind_spans, compute_dependency_spans, Direction,
+  },
+  executable::compute_executable_slice,
+  explain::{explain_dependency, DependencyReason, DependencyStep},
+  kinds::{DependencyKind, DependencyKinds, KindSets, KindedFlowDomain},
 };
 use crate::{
   block_timer,
@@ -23,9 +28,15 @@ use crate::{
 };
 
 mod analysis;
+pub mod cache;
 mod dependencies;
+mod executable;
+mod explain;
+mod kinds;
+pub mod models;
 pub mod mutation;
 mod recursive;
+pub mod summary;
 
 /// The return type of the information flow analysis.
 ///
@@ -50,7 +61,9 @@ pub fn compute_flow<'a, 'tcx>(
   body_id: BodyId,
   body_with_facts: &'a CachedSimplifedBodyWithFacts<'tcx>,
 ) -> FlowResults<'a, 'tcx, TransitiveFlowDomain<'tcx>> {
-  compute_flow_internal(tcx, body_id, body_with_facts)
+  cache::load_or_compute(tcx, body_id, body_with_facts, || {
+    compute_flow_internal(tcx, body_id, body_with_facts)
+  })
 }
 
 pub fn compute_flow_nontransitive<'a, 'tcx>(
@@ -61,10 +74,34 @@ pub fn compute_flow_nontransitive<'a, 'tcx>(
   compute_flow_internal(tcx, body_id, body_with_facts)
 }
 
+/// Computes information flow for a MIR body, labeling each dependency with its kinds.
+///
+/// See [`KindedFlowDomain`] for how the dependencies are labeled.
+pub fn compute_flow_with_kinds<'a, 'tcx>(
+  tcx: TyCtxt<'tcx>,
+  body_id: BodyId,
+  body_with_facts: &'a CachedSimplifedBodyWithFacts<'tcx>,
+) -> FlowResults<'a, 'tcx, KindedFlowDomain<'tcx>> {
+  cache::load_or_compute(tcx, body_id, body_with_facts, || {
+    compute_flow_internal(tcx, body_id, body_with_facts)
+  })
+}
+
 fn compute_flow_internal<'a, 'tcx, D: FlowDomain<'tcx> + JoinSemiLattice>(
   tcx: TyCtxt<'tcx>,
   body_id: BodyId,
   body_with_facts: &'a CachedSimplifedBodyWithFacts<'tcx>,
+) -> FlowResults<'a, 'tcx, D> {
+  compute_flow_instance(tcx, body_id, body_with_facts, None)
+}
+
+/// Computes information flow for a MIR body whose generic parameters are instantiated
+/// with `substs`, which are used to resolve the functions called by the body.
+fn compute_flow_instance<'a, 'tcx, D: FlowDomain<'tcx> + JoinSemiLattice>(
+  tcx: TyCtxt<'tcx>,
+  body_id: BodyId,
+  body_with_facts: &'a CachedSimplifedBodyWithFacts<'tcx>,
+  substs: Option<SubstsRef<'tcx>>,
 ) -> FlowResults<'a, 'tcx, D> {
   BODY_STACK.with(|body_stack| {
     body_stack.borrow_mut().push(body_id);
@@ -86,7 +123,9 @@ fn compute_flow_internal<'a, 'tcx, D: FlowDomain<'tcx> + JoinSemiLattice>(
     let results = {
       block_timer!("Flow");
 
-      let analysis = FlowAnalysis::<D>::new(tcx, def_id, body, aliases, control_dependencies);
+      let mut analysis =
+        FlowAnalysis::<D>::new(tcx, def_id, body, aliases, control_dependencies);
+      analysis.substs = substs;
       engine::iterate_to_fixpoint(tcx, body, location_domain, analysis)
       // analysis.into_engine(tcx, body).iterate_to_fixpoint()
     };
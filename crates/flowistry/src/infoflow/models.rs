@@ -0,0 +1,518 @@
+//! Hand-written flow models of functions, either bundled for commonly used functions
+//! from the standard library or written by users as annotations.
+//!
+//! Without a model, a call is approximated by its signature: the return value depends
+//! on every argument, and every mutable place reachable from any argument could be
+//! mutated by every argument. For example, `v.get_mut(0)` is considered a mutation of `v`,
+//! and `mem::replace(&mut x, y)` returns a value that depends on `y`. A model instead
+//! describes the actual information flow of a function as a [`FlowSummary`], which
+//! [`ModularMutationVisitor`](super::mutation::ModularMutationVisitor) applies in place
+//! of the signature-based approximation.
+//!
+//! Each model is written as a list of flows of the form `place <- inputs`, where
+//! `inputs` is a comma-separated (possibly empty) list of argument positions, and
+//! `place` is one of:
+//! * `ret`: the return value,
+//! * `*i`: the place pointed to by argument `i`, with one `*` per dereference,
+//! * `reach i`: any mutable place reachable from argument `i`.
+//!
+//! The return value depends on no arguments unless the model has a `ret` flow.
+//! Models must be sound with respect to the function's implementation. In particular,
+//! a function that runs a closure argument must account for the closure's mutations
+//! via `reach i`.
+//!
+//! Users can annotate their own functions (e.g. FFI wrappers) with a model via the
+//! `flows` attribute of the `flowistry_annotations` crate, which names arguments instead
+//! of numbering them and groups multiple inputs in parentheses:
+//!
+//! ```ignore
+//! #[flowistry::flows(ret <- a, *b <- (b, c))]
+//! fn f(a: i32, b: &mut i32, c: i32) -> i32 { .. }
+//! ```
+//!
+//! The annotation becomes a `#[flowistry_tool::flows(..)]` attribute when compiling with
+//! [`ANNOTATION_RUSTC_ARGS`], and otherwise has no effect. The annotated crate registers
+//! the [`ANNOTATION_TOOL`] itself, see the documentation of `flowistry_annotations`.
+//! Annotations take precedence over both the bundled models and the analysis of the
+//! function's body.
+
+use std::cell::RefCell;
+
+use log::warn;
+use rustc_ast::Attribute;
+use rustc_ast_pretty::pprust;
+use rustc_data_structures::fx::FxHashMap as HashMap;
+use rustc_hir::{def_id::DefId, definitions::DefPathData};
+use rustc_middle::{
+  mir::Operand,
+  ty::{TyCtxt, TyKind},
+};
+use rustc_span::Symbol;
+
+use super::summary::{FlowSummary, SummaryElem, SummaryPlace};
+use crate::mir::utils;
+
+/// Named models, each a list of flows.
+type Models = &'static [(&'static str, &'static [&'static str])];
+
+/// The flow models, grouped by the path of each function's parent (see [`model_path`]).
+const MODELS: &[(&str, Models)] = &[
+  ("alloc::vec::Vec", &[
+    ("push", &["*0 <- 0, 1"]),
+    ("pop", &["ret <- 0", "*0 <- 0"]),
+    ("insert", &["*0 <- 0, 1, 2"]),
+    ("remove", &["ret <- 0, 1", "*0 <- 0, 1"]),
+    ("swap_remove", &["ret <- 0, 1", "*0 <- 0, 1"]),
+    ("truncate", &["*0 <- 0, 1"]),
+    ("clear", &["*0 <-"]),
+    ("append", &["*0 <- 0, 1", "*1 <-"]),
+    ("extend_from_slice", &["*0 <- 0, 1"]),
+    ("len", &["ret <- 0"]),
+    ("is_empty", &["ret <- 0"]),
+    ("capacity", &["ret <- 0"]),
+    ("as_slice", &["ret <- 0"]),
+    ("as_mut_slice", &["ret <- 0"]),
+    ("deref", &["ret <- 0"]),
+    ("deref_mut", &["ret <- 0"]),
+    ("index", &["ret <- 0, 1"]),
+    ("index_mut", &["ret <- 0, 1"]),
+  ]),
+  ("core::slice::[T]", &[
+    ("len", &["ret <- 0"]),
+    ("is_empty", &["ret <- 0"]),
+    ("first", &["ret <- 0"]),
+    ("first_mut", &["ret <- 0"]),
+    ("last", &["ret <- 0"]),
+    ("last_mut", &["ret <- 0"]),
+    ("get", &["ret <- 0, 1"]),
+    ("get_mut", &["ret <- 0, 1"]),
+    ("iter", &["ret <- 0"]),
+    ("iter_mut", &["ret <- 0"]),
+    ("contains", &["ret <- 0, 1"]),
+    ("swap", &["*0 <- 0, 1, 2"]),
+  ]),
+  ("alloc::string::String", &[
+    ("push", &["*0 <- 0, 1"]),
+    ("push_str", &["*0 <- 0, 1"]),
+    ("pop", &["ret <- 0", "*0 <- 0"]),
+    ("clear", &["*0 <-"]),
+    ("len", &["ret <- 0"]),
+    ("is_empty", &["ret <- 0"]),
+    ("as_str", &["ret <- 0"]),
+    ("deref", &["ret <- 0"]),
+  ]),
+  ("std::collections::hash::map::HashMap", &[
+    ("insert", &["ret <- 0, 1", "*0 <- 0, 1, 2"]),
+    ("remove", &["ret <- 0, 1", "*0 <- 0, 1"]),
+    ("get", &["ret <- 0, 1"]),
+    ("get_mut", &["ret <- 0, 1"]),
+    ("contains_key", &["ret <- 0, 1"]),
+    ("entry", &["ret <- 0, 1"]),
+    ("len", &["ret <- 0"]),
+    ("is_empty", &["ret <- 0"]),
+    ("iter", &["ret <- 0"]),
+    ("keys", &["ret <- 0"]),
+    ("values", &["ret <- 0"]),
+  ]),
+  ("std::collections::hash::set::HashSet", &[
+    ("insert", &["ret <- 0, 1", "*0 <- 0, 1"]),
+    ("remove", &["ret <- 0, 1", "*0 <- 0, 1"]),
+    ("contains", &["ret <- 0, 1"]),
+    ("len", &["ret <- 0"]),
+    ("is_empty", &["ret <- 0"]),
+  ]),
+  ("core::option::Option", &[
+    ("is_some", &["ret <- 0"]),
+    ("is_none", &["ret <- 0"]),
+    ("as_ref", &["ret <- 0"]),
+    ("as_mut", &["ret <- 0"]),
+    ("unwrap", &["ret <- 0"]),
+    ("expect", &["ret <- 0"]),
+    ("unwrap_or", &["ret <- 0, 1"]),
+    ("unwrap_or_default", &["ret <- 0"]),
+    ("ok_or", &["ret <- 0, 1"]),
+    ("take", &["ret <- 0", "*0 <-"]),
+    ("replace", &["ret <- 0", "*0 <- 1"]),
+    ("insert", &["ret <- 0, 1", "*0 <- 1"]),
+    ("get_or_insert", &["ret <- 0, 1", "*0 <- 0, 1"]),
+    ("cloned", &["ret <- 0"]),
+    ("copied", &["ret <- 0"]),
+  ]),
+  ("core::result::Result", &[
+    ("is_ok", &["ret <- 0"]),
+    ("is_err", &["ret <- 0"]),
+    ("ok", &["ret <- 0"]),
+    ("err", &["ret <- 0"]),
+    ("as_ref", &["ret <- 0"]),
+    ("as_mut", &["ret <- 0"]),
+    ("unwrap", &["ret <- 0"]),
+    ("expect", &["ret <- 0"]),
+    ("unwrap_or", &["ret <- 0, 1"]),
+    ("unwrap_or_default", &["ret <- 0"]),
+  ]),
+  ("core::iter::traits::iterator::Iterator", &[
+    ("map", &["ret <- 0, 1"]),
+    ("filter", &["ret <- 0, 1"]),
+    ("filter_map", &["ret <- 0, 1"]),
+    ("enumerate", &["ret <- 0"]),
+    ("zip", &["ret <- 0, 1"]),
+    ("chain", &["ret <- 0, 1"]),
+    ("rev", &["ret <- 0"]),
+    ("skip", &["ret <- 0, 1"]),
+    ("take", &["ret <- 0, 1"]),
+    ("step_by", &["ret <- 0, 1"]),
+    ("peekable", &["ret <- 0"]),
+    ("cloned", &["ret <- 0"]),
+    ("copied", &["ret <- 0"]),
+  ]),
+  ("core::mem", &[
+    ("swap", &["*0 <- 1", "*1 <- 0"]),
+    ("replace", &["ret <- 0", "*0 <- 1"]),
+    ("take", &["ret <- 0", "*0 <-"]),
+  ]),
+];
+
+/// Parses a flow of the form `place <- inputs` (see the module documentation),
+/// where `arg_index` maps the way an argument is written to its position.
+fn parse_flow(
+  flow: &str,
+  arg_index: impl Fn(&str) -> Option<usize>,
+) -> Result<(SummaryPlace, Vec<usize>), String> {
+  let parse_index = |s: &str| {
+    let s = s.trim();
+    arg_index(s).ok_or_else(|| format!("Invalid argument `{s}` in flow: {flow}"))
+  };
+
+  let (place, inputs) = flow
+    .split_once("<-")
+    .ok_or_else(|| format!("Missing `<-` in flow: {flow}"))?;
+
+  let place = place.trim();
+  let place = if place == "ret" {
+    SummaryPlace::Return
+  } else if let Some(index) = place.strip_prefix("reach ") {
+    SummaryPlace::ArgReachable {
+      index: parse_index(index)?,
+    }
+  } else {
+    let index = place.trim_start_matches(|c: char| c == '*' || c.is_whitespace());
+    let num_derefs = place[.. place.len() - index.len()].matches('*').count();
+    SummaryPlace::Arg {
+      index: parse_index(index)?,
+      projection: vec![SummaryElem::Deref; num_derefs],
+    }
+  };
+
+  let inputs = inputs.trim();
+  let inputs = inputs
+    .strip_prefix('(')
+    .and_then(|inputs| inputs.strip_suffix(')'))
+    .unwrap_or(inputs);
+  let inputs = inputs
+    .split(',')
+    .filter(|input| !input.trim().is_empty())
+    .map(parse_index)
+    .collect::<Result<Vec<_>, _>>()?;
+
+  Ok((place, inputs))
+}
+
+/// Parses a model from its list of flows.
+fn parse_model(
+  flows: &[&str],
+  arg_index: impl Fn(&str) -> Option<usize>,
+) -> Result<FlowSummary, String> {
+  let mut summary = FlowSummary {
+    flows: flows
+      .iter()
+      .map(|flow| parse_flow(flow, &arg_index))
+      .collect::<Result<_, _>>()?,
+  };
+  summary.join(&FlowSummary {
+    flows: vec![(SummaryPlace::Return, Vec::new())],
+  });
+  Ok(summary)
+}
+
+fn parse_position(s: &str) -> Option<usize> {
+  s.parse().ok()
+}
+
+thread_local! {
+  static PARSED_MODELS: HashMap<String, FlowSummary> = MODELS
+    .iter()
+    .flat_map(|(parent, models)| {
+      models.iter().map(move |(name, flows)| {
+        let path = format!("{parent}::{name}");
+        let model = parse_model(flows, parse_position)
+          .unwrap_or_else(|err| panic!("{path}: {err}"));
+        (path, model)
+      })
+    })
+    .collect();
+}
+
+/// Returns the path of `def_id` as used in [`MODELS`], e.g. `alloc::vec::Vec::push`.
+///
+/// Paths are built from the crate where `def_id` is defined rather than from where it is
+/// re-exported, and methods are named after the self type of their impl.
+fn model_path(tcx: TyCtxt<'_>, def_id: DefId) -> String {
+  let key = tcx.def_key(def_id);
+  let parent = match key.parent {
+    Some(index) => DefId {
+      krate: def_id.krate,
+      index,
+    },
+    None => return tcx.crate_name(def_id.krate).to_string(),
+  };
+
+  match key.disambiguated_data.data {
+    DefPathData::Impl => {
+      let self_ty = tcx.type_of(def_id);
+      match self_ty.kind() {
+        TyKind::Adt(adt_def, _) => model_path(tcx, adt_def.did()),
+        _ => format!("{}::{self_ty}", model_path(tcx, parent)),
+      }
+    }
+    data => match data.get_opt_name() {
+      Some(name) => format!("{}::{name}", model_path(tcx, parent)),
+      None => model_path(tcx, parent),
+    },
+  }
+}
+
+/// The tool that `flows` annotations are registered under.
+pub const ANNOTATION_TOOL: &str = "flowistry_tool";
+
+/// Arguments to rustc that enable the annotations of the `flowistry_annotations` crate.
+///
+/// The tool is not registered by these arguments, since a crate that registers it
+/// itself would fail to compile, so each annotated crate must register it.
+pub const ANNOTATION_RUSTC_ARGS: &[&str] = &["--cfg", "flowistry"];
+
+fn is_flows_attr(attr: &Attribute) -> bool {
+  if attr.is_doc_comment() {
+    return false;
+  }
+  let segments = &attr.get_normal_item().path.segments;
+  segments.len() == 2
+    && segments[0].ident.name == Symbol::intern(ANNOTATION_TOOL)
+    && segments[1].ident.name == Symbol::intern("flows")
+}
+
+/// Splits `s` at each comma that is not nested in parentheses.
+fn split_flows(s: &str) -> Vec<&str> {
+  let mut flows = Vec::new();
+  let mut depth = 0;
+  let mut start = 0;
+  for (i, c) in s.char_indices() {
+    match c {
+      '(' => depth += 1,
+      ')' => depth -= 1,
+      ',' if depth == 0 => {
+        flows.push(&s[start .. i]);
+        start = i + 1;
+      }
+      _ => {}
+    }
+  }
+  flows.push(&s[start ..]);
+  flows
+    .into_iter()
+    .filter(|flow| !flow.trim().is_empty())
+    .collect()
+}
+
+thread_local! {
+  static ANNOTATIONS: RefCell<HashMap<DefId, Option<FlowSummary>>> =
+    RefCell::new(HashMap::default());
+}
+
+/// Returns the model given by the `flows` annotation of the function `def_id`, if any.
+pub fn annotated_flows(tcx: TyCtxt<'_>, def_id: DefId) -> Option<FlowSummary> {
+  if let Some(flows) =
+    ANNOTATIONS.with(|annotations| annotations.borrow().get(&def_id).cloned())
+  {
+    return flows;
+  }
+
+  let flows = tcx
+    .get_attrs_unchecked(def_id)
+    .iter()
+    .find(|attr| is_flows_attr(attr))
+    .and_then(|attr| {
+      let tokens = pprust::tts_to_string(&attr.get_normal_item().args.inner_tokens());
+      let arg_names = tcx.fn_arg_names(def_id);
+      let arg_index = |name: &str| arg_names.iter().position(|arg| arg.as_str() == name);
+      match parse_model(&split_flows(&tokens), arg_index) {
+        Ok(flows) => Some(flows),
+        Err(err) => {
+          warn!("Ignoring flows annotation on {def_id:?}: {err}");
+          None
+        }
+      }
+    });
+
+  ANNOTATIONS.with(|annotations| annotations.borrow_mut().insert(def_id, flows.clone()));
+  flows
+}
+
+/// Returns the model of the function called by `func` from within `caller`, if any.
+pub fn get_model<'tcx>(
+  tcx: TyCtxt<'tcx>,
+  caller: DefId,
+  func: &Operand<'tcx>,
+) -> Option<FlowSummary> {
+  let (def_id, _) = utils::resolve_callee(tcx, caller, func)?;
+  if let Some(flows) = annotated_flows(tcx, def_id) {
+    return Some(flows);
+  }
+
+  if def_id.is_local() {
+    return None;
+  }
+
+  let path = model_path(tcx, def_id);
+  PARSED_MODELS.with(|models| models.get(&path).cloned())
+}
+
+#[cfg(test)]
+mod test {
+  use std::{
+    env::{
+      self,
+      consts::{DLL_PREFIX, DLL_SUFFIX},
+    },
+    fs,
+    path::PathBuf,
+    process,
+  };
+
+  use rustc_middle::mir::{Local, Place};
+
+  use super::*;
+  use crate::{
+    infoflow,
+    mir::{borrowck_facts, utils::BodyExt},
+    test_utils,
+  };
+
+  #[test]
+  fn test_parse_models() {
+    for (parent, models) in MODELS {
+      for (name, flows) in *models {
+        if let Err(err) = parse_model(flows, parse_position) {
+          panic!("{parent}::{name}: {err}");
+        }
+      }
+    }
+
+    assert_eq!(
+      parse_model(&["ret <- 0", "**1 <- 0, 2", "reach 2 <-"], parse_position).unwrap(),
+      FlowSummary {
+        flows: vec![
+          (SummaryPlace::Return, vec![0]),
+          (
+            SummaryPlace::Arg {
+              index: 1,
+              projection: vec![SummaryElem::Deref, SummaryElem::Deref]
+            },
+            vec![0, 2]
+          ),
+          (SummaryPlace::ArgReachable { index: 2 }, vec![]),
+        ]
+      }
+    );
+
+    assert!(parse_flow("*0 -> 1", parse_position).is_err());
+    assert!(parse_flow("*x <- 1", parse_position).is_err());
+    assert!(parse_flow("ret <- 0 1", parse_position).is_err());
+  }
+
+  #[test]
+  fn test_parse_annotation() {
+    let arg_index = |name: &str| ["a", "b", "c"].iter().position(|arg| *arg == name);
+    assert_eq!(
+      parse_model(&split_flows("ret <- a, *b <- (b, c)"), arg_index).unwrap(),
+      FlowSummary {
+        flows: vec![
+          (SummaryPlace::Return, vec![0]),
+          (
+            SummaryPlace::Arg {
+              index: 1,
+              projection: vec![SummaryElem::Deref]
+            },
+            vec![1, 2]
+          ),
+        ]
+      }
+    );
+
+    assert!(parse_model(&split_flows("ret <- d"), arg_index).is_err());
+  }
+
+  /// Returns the proc-macro library of the `flowistry_annotations` crate, which Cargo
+  /// builds next to the tests as a dev-dependency.
+  fn annotations_library() -> PathBuf {
+    let deps_dir = env::current_exe().unwrap().parent().unwrap().to_path_buf();
+    let prefix = format!("{DLL_PREFIX}flowistry_annotations-");
+    fs::read_dir(deps_dir)
+      .unwrap()
+      .filter_map(|entry| Some(entry.ok()?.path()))
+      .filter(|path| {
+        let name = path.file_name().unwrap().to_string_lossy();
+        name.starts_with(&prefix) && name.ends_with(DLL_SUFFIX)
+      })
+      .max_by_key(|path| fs::metadata(path).unwrap().modified().unwrap())
+      .expect("flowistry_annotations is not built")
+  }
+
+  #[test]
+  fn test_flows_proc_macro() {
+    let input = r#"
+#![cfg_attr(flowistry, feature(register_tool), register_tool(flowistry_tool))]
+use flowistry_annotations as flowistry;
+
+#[flowistry::flows(ret <- a)]
+fn g(a: i32, b: i32) -> i32 { a + b }
+
+pub fn f(a: i32, b: i32) -> i32 { g(a, b) }
+"#;
+    let dir = env::temp_dir().join(format!("flowistry-annotations-{}", process::id()));
+    let library = annotations_library();
+    let extern_arg = format!("flowistry_annotations={}", library.display());
+    let mut args = vec!["--extern", &extern_arg];
+    args.extend(ANNOTATION_RUSTC_ARGS);
+    test_utils::compile_crate(input, "annotated", &dir, &args, |tcx| {
+      let hir = tcx.hir();
+      let body_owner = |name: &str| {
+        hir
+          .body_owners()
+          .find(|def_id| tcx.item_name(def_id.to_def_id()).as_str() == name)
+          .unwrap()
+      };
+
+      let g = body_owner("g").to_def_id();
+      assert_eq!(
+        annotated_flows(tcx, g),
+        Some(FlowSummary {
+          flows: vec![(SummaryPlace::Return, vec![0])]
+        })
+      );
+
+      let f = body_owner("f");
+      let body_id = hir.body_owned_by(hir.local_def_id_to_hir_id(f));
+      let body_with_facts = borrowck_facts::get_body_with_borrowck_facts(tcx, f);
+      let results = infoflow::compute_flow(tcx, body_id, body_with_facts);
+      let location_domain = results.analysis.location_domain();
+      let body = body_with_facts.simplified_body();
+      for location in body.all_returns() {
+        let deps = results.state_at(location).row_set(Place::return_place());
+        assert!(deps.contains(location_domain.arg_to_location(Local::from_usize(1))));
+        assert!(!deps.contains(location_domain.arg_to_location(Local::from_usize(2))));
+      }
+    });
+
+    fs::remove_dir_all(&dir).unwrap();
+  }
+}
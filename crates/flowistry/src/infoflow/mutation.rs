@@ -1,6 +1,7 @@
 use log::debug;
 use rustc_middle::mir::{visit::Visitor, *};
 
+use super::models;
 use crate::mir::{
   aliases::Aliases,
   utils::{self, OperandExt, PlaceCollector},
@@ -18,7 +19,7 @@ pub struct ModularMutationVisitor<'a, 'tcx, F>
 where
   F: FnMut(
     Place<'tcx>,
-    &[(Place<'tcx>, Option<PlaceElem<'tcx>>)],
+    &[(Place<'tcx>, Option<&'tcx [PlaceElem<'tcx>]>)],
     Location,
     MutationStatus,
   ),
@@ -31,7 +32,7 @@ impl<'a, 'tcx, F> ModularMutationVisitor<'a, 'tcx, F>
 where
   F: FnMut(
     Place<'tcx>,
-    &[(Place<'tcx>, Option<PlaceElem<'tcx>>)],
+    &[(Place<'tcx>, Option<&'tcx [PlaceElem<'tcx>]>)],
     Location,
     MutationStatus,
   ),
@@ -39,13 +40,60 @@ where
   pub fn new(aliases: &'a Aliases<'a, 'tcx>, f: F) -> Self {
     ModularMutationVisitor { aliases, f }
   }
+
+  /// Applies the effects of a call on the mutable globals that the callee may access
+  /// (see [`Globals`](crate::mir::globals::Globals)). The call's return value may
+  /// depend on each global, and each global may be mutated by the call's inputs.
+  pub fn visit_call_globals(&mut self, call: &TerminatorKind<'tcx>, location: Location) {
+    let (args, destination) = match call {
+      TerminatorKind::Call {
+        args, destination, ..
+      } => (args, destination),
+      _ => return,
+    };
+
+    let tcx = self.aliases.tcx;
+    let globals = self.aliases.globals().accessed_by_call(tcx, args, location);
+    if globals.is_empty() {
+      return;
+    }
+    debug!("  Call may access globals {globals:?}");
+
+    let global_inputs = globals
+      .iter()
+      .map(|global| (*global, None))
+      .collect::<Vec<_>>();
+    if let Some((dst_place, _)) = destination {
+      let ret_is_unit = dst_place
+        .ty(self.aliases.body.local_decls(), tcx)
+        .ty
+        .is_unit();
+      if !ret_is_unit {
+        (self.f)(
+          *dst_place,
+          &global_inputs,
+          location,
+          MutationStatus::Possibly,
+        );
+      }
+    }
+
+    let inputs = utils::arg_places(args)
+      .into_iter()
+      .map(|(_, place)| (place, None))
+      .chain(global_inputs)
+      .collect::<Vec<_>>();
+    for global in globals {
+      (self.f)(global, &inputs, location, MutationStatus::Possibly);
+    }
+  }
 }
 
 impl<'tcx, F> Visitor<'tcx> for ModularMutationVisitor<'_, 'tcx, F>
 where
   F: FnMut(
     Place<'tcx>,
-    &[(Place<'tcx>, Option<PlaceElem<'tcx>>)],
+    &[(Place<'tcx>, Option<&'tcx [PlaceElem<'tcx>]>)],
     Location,
     MutationStatus,
   ),
@@ -76,14 +124,32 @@ where
 
     match &terminator.kind {
       TerminatorKind::Call {
-        /*func,*/ // TODO: deal with func
+        func,
         args,
         destination,
         ..
       } => {
+        if let Some(model) = models::get_model(tcx, self.aliases.def_id, func) {
+          debug!("  Applying model {model:?}");
+          let f = &mut self.f;
+          model.apply(
+            self.aliases,
+            args,
+            destination.map(|(dst, _)| dst),
+            |mutated, inputs, mutation_status| {
+              f(mutated, inputs, location, mutation_status)
+            },
+          );
+          self.visit_call_globals(&terminator.kind, location);
+          return;
+        }
+
+        // If the function is called through a place, e.g. a function pointer, then
+        // the function (along with any state it captures) is treated like an argument
         let arg_places = utils::arg_places(args)
           .into_iter()
           .map(|(_, place)| place)
+          .chain(func.to_place())
           .collect::<Vec<_>>();
         let arg_inputs = arg_places
           .iter()
@@ -112,6 +178,8 @@ where
             (self.f)(*arg_mut, &arg_inputs, location, MutationStatus::Possibly);
           }
         }
+
+        self.visit_call_globals(&terminator.kind, location);
       }
 
       TerminatorKind::DropAndReplace { place, value, .. } => {
@@ -120,6 +188,12 @@ where
         }
       }
 
+      // The value passed back into a generator when it is resumed comes from the
+      // generator's caller, so it does not depend on anything in the body
+      TerminatorKind::Yield { resume_arg, .. } => {
+        (self.f)(*resume_arg, &[], location, MutationStatus::Definitely);
+      }
+
       _ => {}
     }
   }
@@ -1,20 +1,42 @@
+use std::iter;
+
 use log::{debug, info};
 use rustc_middle::{
   mir::*,
-  ty::{subst::GenericArgKind, ClosureKind, TyKind},
+  ty::{subst::GenericArgKind, ClosureKind, Ty, TyKind, TypeFoldable},
 };
 
-use super::{analysis::FlowAnalysis, BODY_STACK};
+use super::{analysis::FlowAnalysis, models, FlowResults, BODY_STACK};
 use crate::{
   extensions::{REACHED_LIBRARY, RECURSE_SELECTOR},
   infoflow::{mutation::MutationStatus, FlowDomain},
   mir::{
     borrowck_facts::get_body_with_borrowck_facts,
-    utils::{self, PlaceExt},
+    utils::{self, BodyExt, PlaceExt},
   },
 };
 
+fn is_mut_closure(ty: Ty<'_>) -> bool {
+  match ty.kind() {
+    TyKind::Closure(_, substs) => matches!(
+      substs.as_closure().kind(),
+      ClosureKind::FnOnce | ClosureKind::FnMut
+    ),
+    _ => false,
+  }
+}
+
 impl<'tcx, D: FlowDomain<'tcx>> FlowAnalysis<'_, 'tcx, D> {
+  crate fn any_closure_inputs(&self, arg_places: &[(usize, Place<'tcx>)]) -> bool {
+    arg_places.iter().any(|(_, place)| {
+      let ty = place.ty(self.body.local_decls(), self.tcx).ty;
+      ty.walk().any(|arg| match arg.unpack() {
+        GenericArgKind::Type(ty) => is_mut_closure(ty),
+        _ => false,
+      })
+    })
+  }
+
   crate fn recurse_into_call(
     &self,
     state: &mut D,
@@ -37,44 +59,51 @@ impl<'tcx, D: FlowDomain<'tcx>> FlowAnalysis<'_, 'tcx, D> {
     };
     debug!("Checking whether can recurse into {func:?}");
 
-    let func = match func.constant() {
-      Some(func) => func,
+    let callee = utils::resolve_callee_with_substs(tcx, self.def_id, func, self.substs);
+    let (def_id, substs) = match callee {
+      Some(callee) => callee,
       None => {
-        debug!("  Func is not constant");
+        debug!("  Func is not a constant FnDef");
         return false;
       }
     };
 
-    let def_id = match func.literal.ty().kind() {
-      TyKind::FnDef(def_id, _) => def_id,
-      _ => {
-        debug!("  Func is not a FnDef");
-        return false;
-      }
-    };
+    // Annotated flows take precedence over the callee's body
+    if let Some(flows) = models::annotated_flows(tcx, def_id) {
+      debug!("  Applying annotated flows of func");
+      let destination = destination.map(|(dst, _)| dst);
+      self.apply_summary(state, &flows, parent_args, destination, location);
+      return true;
+    }
+
+    let is_closure = tcx.is_closure(def_id);
 
-    // If a function returns never (fn () -> !) then there are no exit points,
-    // so we can't analyze effects on exit
-    let fn_sig = tcx.fn_sig(*def_id);
+    // If a function returns never (fn () -> !) then there are no return points,
+    // so we can't analyze effects on return. The states at the callee's unwinding
+    // and diverging exits never reach the caller's return successor, so they are
+    // not used even with ExitMode::AllExits.
+    let fn_sig = if is_closure {
+      substs.as_closure().sig()
+    } else {
+      tcx.fn_sig(def_id)
+    };
     if fn_sig.skip_binder().output().is_never() {
       debug!("  Func returns never");
       return false;
     }
 
-    let node = match tcx.hir().get_if_local(*def_id) {
-      Some(node) => node,
-      None => {
-        debug!("  Func is not in local crate");
-        REACHED_LIBRARY.get(|reached_library| {
-          if let Some(reached_library) = reached_library {
-            *reached_library.borrow_mut() = true;
-          }
-        });
-        return false;
-      }
-    };
+    if tcx.hir().get_if_local(def_id).is_none() {
+      debug!("  Func is not in local crate");
+      REACHED_LIBRARY.get(|reached_library| {
+        if let Some(reached_library) = reached_library {
+          *reached_library.borrow_mut() = true;
+        }
+      });
+      return false;
+    }
 
-    let body_id = match node.body_id() {
+    let hir_id = tcx.hir().local_def_id_to_hir_id(def_id.expect_local());
+    let body_id = match tcx.hir().maybe_body_owned_by(hir_id) {
       Some(body_id) => body_id,
       None => {
         debug!("  Func does not have a BodyId");
@@ -89,21 +118,20 @@ impl<'tcx, D: FlowDomain<'tcx>> FlowAnalysis<'_, 'tcx, D> {
     }
 
     let parent_arg_places = utils::arg_places(parent_args);
-    let any_closure_inputs = parent_arg_places.iter().any(|(_, place)| {
-      let ty = place.ty(self.body.local_decls(), tcx).ty;
-      ty.walk().any(|arg| match arg.unpack() {
-        GenericArgKind::Type(ty) => match ty.kind() {
-          TyKind::Closure(_, substs) => matches!(
-            substs.as_closure().kind(),
-            ClosureKind::FnOnce | ClosureKind::FnMut
-          ),
-          _ => false,
-        },
-        _ => false,
-      })
-    });
-    if any_closure_inputs {
-      debug!("  Func has closure inputs");
+    // A closure's environment is handled by recursing into the closure itself
+    let non_env_arg_places = parent_arg_places
+      .iter()
+      .filter(|(j, _)| !is_closure || *j != 0)
+      .copied()
+      .collect::<Vec<_>>();
+    // The callee is analyzed with the types it is instantiated with, so that the
+    // closures passed to the callee are resolved wherever the callee calls them.
+    // If the types are not fully known, then the calls to a closure could only be
+    // approximated from the closure's signature, which ignores its captures.
+    let callee_substs = tcx.erase_regions(substs);
+    let callee_substs = (!callee_substs.needs_subst()).then(|| callee_substs);
+    if callee_substs.is_none() && self.any_closure_inputs(&non_env_arg_places) {
+      debug!("  Func has closure inputs of unknown types");
       return false;
     }
 
@@ -118,26 +146,21 @@ impl<'tcx, D: FlowDomain<'tcx>> FlowAnalysis<'_, 'tcx, D> {
 
     let body_with_facts = get_body_with_borrowck_facts(tcx, def_id.expect_local());
     let mut recurse_cache = self.recurse_cache.borrow_mut();
-    let flow = recurse_cache.entry(body_id).or_insert_with(|| {
-      info!("Recursing into {}", tcx.def_path_debug_str(*def_id));
-      super::compute_flow_internal(tcx, body_id, body_with_facts)
-    });
-    let body = &body_with_facts.simplified_body();
-
-    let mut return_state = D::from_location_domain(flow.analysis.location_domain());
-    {
-      let return_locs = body
-        .basic_blocks()
-        .iter_enumerated()
-        .filter_map(|(bb, data)| match data.terminator().kind {
-          TerminatorKind::Return => Some(body.terminator_loc(bb)),
-          _ => None,
-        });
-
-      for loc in return_locs {
-        return_state.join(flow.state_at(loc));
-      }
-    };
+    let flow = recurse_cache
+      .entry((body_id, callee_substs))
+      .or_insert_with(|| {
+        info!("Recursing into {}", tcx.def_path_debug_str(def_id));
+        super::compute_flow_instance(tcx, body_id, body_with_facts, callee_substs)
+      });
+    let body = body_with_facts.simplified_body();
+
+    let return_state = return_state(flow, body);
+
+    // The callee cannot see inside of values whose type is generic in its body, e.g. a
+    // closure `f: F`, so their mutations are truncated to the generic value. Such a
+    // mutation may reach anything mutable in the concrete value, even if passed by value.
+    let is_generic =
+      |child: Place<'tcx>| child.ty(body.local_decls(), tcx).ty.needs_subst();
 
     let translate_child_to_parent = |child: Place<'tcx>,
                                      mutated: bool|
@@ -152,7 +175,7 @@ impl<'tcx, D: FlowDomain<'tcx>> FlowAnalysis<'_, 'tcx, D> {
         }
       }
 
-      if !child.is_arg(body) || (mutated && !child.is_indirect()) {
+      if !child.is_arg(body) || (mutated && !child.is_indirect() && !is_generic(child)) {
         return None;
       }
 
@@ -162,35 +185,56 @@ impl<'tcx, D: FlowDomain<'tcx>> FlowAnalysis<'_, 'tcx, D> {
       //    parent_arg_projected = (*_5.0).1
       //    parent_arg_accessible = (*_5.0)
 
-      let parent_toplevel_arg = parent_arg_places
-        .iter()
-        .find(|(j, _)| child.local.as_usize() - 1 == *j)
-        .map(|(_, place)| place)?;
+      let parent_arg = |j: usize| {
+        parent_arg_places
+          .iter()
+          .find(|(k, _)| *k == j)
+          .map(|(_, place)| *place)
+      };
+      let child_index = child.local.as_usize() - 1;
+      let parent_toplevel_arg = if is_closure && child_index > 0 {
+        // A closure is called with its arguments packed into a tuple,
+        // which the closure's body receives as separate locals
+        let tuple = parent_arg(1)?;
+        let field = Field::from_usize(child_index - 1);
+        let tuple_ty = tuple.ty(self.body.local_decls(), tcx);
+        tcx.mk_place_field(tuple, field, utils::field_ty(tcx, tuple_ty, field)?)
+      } else {
+        parent_arg(child_index)?
+      };
 
-      let mut projection = parent_toplevel_arg.projection.to_vec();
-      let mut ty = parent_toplevel_arg.ty(self.body.local_decls(), tcx);
-      let parent_param_env = tcx.param_env(self.def_id);
-      log::debug!("Adding child {child:?} to parent {parent_toplevel_arg:?}");
-      for elem in child.projection.iter() {
-        ty = ty.projection_ty_core(tcx, parent_param_env, &elem, |_, field, _| {
-          ty.field_ty(tcx, field)
-        });
-        let elem = match elem {
-          ProjectionElem::Field(field, _) => ProjectionElem::Field(field, ty.ty),
-          elem => elem,
-        };
-        projection.push(elem);
+      // A closure that takes its environment by reference can be called through
+      // FnOnce with the closure itself, in which case the reference is skipped
+      let mut child_projection = &child.projection[..];
+      if is_closure && child_index == 0 {
+        let parent_ty = parent_toplevel_arg.ty(self.body.local_decls(), tcx).ty;
+        let child_ty = body.local_decls[child.local].ty;
+        if child_ty.is_ref() && !parent_ty.is_ref() {
+          match child_projection.split_first() {
+            Some((ProjectionElem::Deref, rest)) => child_projection = rest,
+            _ => return None,
+          }
+        }
       }
 
-      let parent_arg_projected = Place::make(parent_toplevel_arg.local, &projection, tcx);
+      log::debug!("Adding child {child:?} to parent {parent_toplevel_arg:?}");
+      let parent_arg_projected = self.project(parent_toplevel_arg, child_projection);
       Some(parent_arg_projected)
     };
 
+    let location_domain = flow.analysis.location_domain();
     for (child, _) in return_state.matrix().rows() {
       if let Some(parent) = translate_child_to_parent(child, true) {
         let was_return = child.local == RETURN_PLACE;
-        // > 1 because arguments will always have their synthetic location in their dep set
-        let was_mutated = return_state.row(child).len() > 1;
+        // Arguments always start with their synthetic location in their dep set,
+        // so any other location indicates that the argument was mutated
+        let was_mutated = !was_return && {
+          let arg_location = location_domain.arg_to_location(child.local);
+          return_state
+            .row(child)
+            .indices()
+            .any(|loc| loc != arg_location)
+        };
         if !was_mutated && !was_return {
           continue;
         }
@@ -207,20 +251,91 @@ impl<'tcx, D: FlowDomain<'tcx>> FlowAnalysis<'_, 'tcx, D> {
           "child {child:?} \n  / child_deps {child_deps:?}\n-->\nparent {parent:?}\n   / parent_deps {parent_deps:?}"
         );
 
-        self.transfer_function(
-          state,
-          parent,
-          &parent_deps,
-          location,
-          if was_return {
-            MutationStatus::Definitely
-          } else {
-            MutationStatus::Possibly
-          },
-        );
+        let parents = if !was_return && is_generic(child) {
+          let reachable = self.aliases.reachable_values(parent, Mutability::Mut);
+          reachable.iter().copied().collect::<Vec<_>>()
+        } else if !was_return {
+          iter::once(parent)
+            .chain(self.generic_sources(parent))
+            .collect()
+        } else {
+          vec![parent]
+        };
+        for parent in parents {
+          self.transfer_function(
+            state,
+            parent,
+            &parent_deps,
+            location,
+            if was_return {
+              MutationStatus::Definitely
+            } else {
+              MutationStatus::Possibly
+            },
+          );
+        }
       }
     }
 
     true
   }
+
+  /// If `place` is a value of a type parameter that is not an argument, e.g. a temporary
+  /// that a closure `f: F` is moved into, then returns the arguments it could come from,
+  /// since the body has no other way of constructing values of that type.
+  fn generic_sources(&self, place: Place<'tcx>) -> Vec<Place<'tcx>> {
+    let tcx = self.tcx;
+    let ty = place.ty(self.body.local_decls(), tcx).ty;
+    if !matches!(ty.kind(), TyKind::Param(_)) || place.is_arg(self.body) {
+      return Vec::new();
+    }
+
+    self
+      .body
+      .args_iter()
+      .filter_map(|arg| {
+        let arg_ty = self.body.local_decls[arg].ty;
+        if arg_ty == ty {
+          Some(Place::from(arg))
+        } else if arg_ty.builtin_deref(true).map(|tm| tm.ty) == Some(ty) {
+          Some(tcx.mk_place_deref(Place::from(arg)))
+        } else {
+          None
+        }
+      })
+      .collect()
+  }
+
+  /// Appends `projection` to `base`, computing the types of fields from the caller's
+  /// view of `base`. If `base` is opaque to the caller, e.g. a type parameter, then
+  /// the projection stops at the opaque place.
+  fn project(&self, base: Place<'tcx>, projection: &[PlaceElem<'tcx>]) -> Place<'tcx> {
+    let tcx = self.tcx;
+    let param_env = tcx.param_env(self.def_id);
+    let mut elems = base.projection.to_vec();
+    let mut ty = base.ty(self.body.local_decls(), tcx);
+    for elem in projection {
+      let elem = match *elem {
+        ProjectionElem::Field(field, _) => match utils::field_ty(tcx, ty, field) {
+          Some(field_ty) => ProjectionElem::Field(field, field_ty),
+          None => break,
+        },
+        elem => elem,
+      };
+      ty = ty.projection_ty_core(tcx, param_env, &elem, |_, _, field_ty| field_ty);
+      elems.push(elem);
+    }
+    Place::make(base.local, &elems, tcx)
+  }
+}
+
+fn return_state<'tcx, D: FlowDomain<'tcx>>(
+  flow: &FlowResults<'_, 'tcx, D>,
+  body: &Body<'tcx>,
+) -> D {
+  let mut return_state = D::from_location_domain(flow.analysis.location_domain());
+  for loc in body.all_returns() {
+    return_state.join(flow.state_at(loc));
+  }
+  return_state
 }
@@ -0,0 +1,907 @@
+//! Per-function summaries of information flow, used by [`ContextMode::Summary`].
+//!
+//! A summary describes how a function's arguments flow into its return value and
+//! into the places it mutates through pointer arguments. Summaries refer to places
+//! relative to the function's signature rather than using rustc types, so each function
+//! is analyzed once and its summary is reused at every call site.
+//!
+//! Summaries can also be exported with [`export_summaries`], so that if a [`SUMMARY_DIR`]
+//! is set, then functions from other crates are analyzed using their exported summary.
+//!
+//! [`ContextMode::Summary`]: crate::extensions::ContextMode::Summary
+
+use std::{
+  cell::RefCell,
+  collections::BTreeMap,
+  fs,
+  path::{Path, PathBuf},
+  rc::Rc,
+  time::SystemTime,
+};
+
+use anyhow::Result;
+use fluid_let::fluid_let;
+use log::{debug, info, warn};
+use rustc_data_structures::fx::FxHashMap as HashMap;
+use rustc_hir::{
+  def::DefKind,
+  def_id::{CrateNum, DefId, LocalDefId, LOCAL_CRATE},
+};
+use rustc_middle::{
+  mir::*,
+  ty::{ParamEnv, TyCtxt, TyKind, TypeFoldable},
+};
+use rustc_mir_dataflow::JoinSemiLattice;
+use rustc_target::abi::VariantIdx;
+use serde::{Deserialize, Serialize};
+
+use super::{
+  analysis::FlowAnalysis, models, mutation::MutationStatus, FlowDomain, FlowResults,
+  TransitiveFlowDomain,
+};
+use crate::{
+  extensions::{EvalMode, EVAL_MODE},
+  mir::{
+    aliases::Aliases,
+    borrowck_facts::get_body_with_borrowck_facts,
+    call_graph::CallGraph,
+    utils::{self, BodyExt, OperandExt, PlaceExt},
+  },
+};
+
+/// A projection element of a [`SummaryPlace`], analogous to [`ProjectionElem`].
+#[derive(
+  Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub enum SummaryElem {
+  Deref,
+  Field(usize),
+  /// An unknown index, which stands for any element of an array or slice.
+  Index,
+  ConstantIndex {
+    offset: u64,
+    from_end: bool,
+  },
+  Downcast(usize),
+}
+
+impl SummaryElem {
+  fn from_elem(elem: PlaceElem<'_>) -> Option<Self> {
+    match elem {
+      ProjectionElem::Deref => Some(SummaryElem::Deref),
+      ProjectionElem::Field(field, _) => Some(SummaryElem::Field(field.as_usize())),
+      ProjectionElem::Index(_) => Some(SummaryElem::Index),
+      ProjectionElem::ConstantIndex {
+        offset, from_end, ..
+      } => Some(SummaryElem::ConstantIndex { offset, from_end }),
+      ProjectionElem::Downcast(_, variant) => {
+        Some(SummaryElem::Downcast(variant.as_usize()))
+      }
+      // The indices of elements after a subslice are relative to the subslice, so
+      // its place is approximated by the sliced array or slice
+      ProjectionElem::Subslice { .. } => None,
+    }
+  }
+}
+
+/// A place that is visible to the caller of a function.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum SummaryPlace {
+  /// The return value of the function.
+  Return,
+
+  /// A place rooted in the argument at position `index` (starting from 0).
+  Arg {
+    index: usize,
+    projection: Vec<SummaryElem>,
+  },
+
+  /// Any mutable place reachable from the argument at position `index`.
+  ///
+  /// Used in place of arbitrarily deep places that arise from recursive functions,
+  /// e.g. a function that mutates each node of a tree.
+  ArgReachable { index: usize },
+}
+
+/// The information flow of a function as seen by its callers.
+///
+/// Each entry of `flows` pairs a place that the function writes with the positions
+/// of the arguments that could influence it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct FlowSummary {
+  pub flows: Vec<(SummaryPlace, Vec<usize>)>,
+}
+
+impl FlowSummary {
+  /// Summarizes the results of the information flow analysis of `body`.
+  pub fn from_results<'tcx>(
+    body: &Body<'tcx>,
+    results: &FlowResults<'_, 'tcx, TransitiveFlowDomain<'tcx>>,
+  ) -> Self {
+    let tcx = results.analysis.tcx;
+    let location_domain = results.analysis.location_domain();
+    let mut return_state = TransitiveFlowDomain::from_location_domain(location_domain);
+    for loc in body.all_returns() {
+      return_state.join(results.state_at(loc));
+    }
+
+    let arg_deps = |place: Place<'tcx>| {
+      let mut args = return_state
+        .row(place)
+        .filter_map(|loc| location_domain.location_to_local(loc))
+        .map(|local| local.as_usize() - 1)
+        .collect::<Vec<_>>();
+      args.sort_unstable();
+      args.dedup();
+      args
+    };
+
+    let mut summary = FlowSummary {
+      flows: vec![(SummaryPlace::Return, arg_deps(Place::return_place()))],
+    };
+    for (place, deps) in return_state.rows() {
+      if !place.is_arg(body) {
+        continue;
+      }
+
+      // The function cannot see inside of values whose type is generic in its body,
+      // so a mutation of such a value may reach anything mutable in the concrete
+      // value, even if passed by value (as in the Recurse mode)
+      let is_generic = place.ty(body.local_decls(), tcx).ty.needs_subst();
+      if !place.is_indirect() && !is_generic {
+        continue;
+      }
+
+      // Arguments always start with their synthetic location in their dep set,
+      // so any other location indicates that the place was mutated
+      let arg_location = location_domain.arg_to_location(place.local);
+      let was_mutated = deps.indices().any(|loc| loc != arg_location);
+      if !was_mutated {
+        continue;
+      }
+
+      let index = place.local.as_usize() - 1;
+      let summary_place = if is_generic {
+        SummaryPlace::ArgReachable { index }
+      } else {
+        // A prefix of the place is a conservative approximation of it, so the
+        // projection stops at the first element that has no summary equivalent
+        SummaryPlace::Arg {
+          index,
+          projection: place
+            .projection
+            .iter()
+            .map_while(SummaryElem::from_elem)
+            .collect(),
+        }
+      };
+      summary.join(&FlowSummary {
+        flows: vec![(summary_place, arg_deps(place))],
+      });
+    }
+
+    summary
+  }
+
+  /// Adds the flows of `other` into `self`, returning true if `self` changed.
+  pub fn join(&mut self, other: &FlowSummary) -> bool {
+    let mut changed = false;
+    for (place, deps) in &other.flows {
+      match self
+        .flows
+        .iter_mut()
+        .find(|(existing, _)| existing == place)
+      {
+        Some((_, existing_deps)) => {
+          for dep in deps {
+            if !existing_deps.contains(dep) {
+              existing_deps.push(*dep);
+              changed = true;
+            }
+          }
+          existing_deps.sort_unstable();
+        }
+        None => {
+          self.flows.push((place.clone(), deps.clone()));
+          changed = true;
+        }
+      }
+    }
+    self.flows.sort();
+    changed
+  }
+
+  /// Replaces argument places with more than `max_derefs` dereferences by
+  /// [`SummaryPlace::ArgReachable`], so that summaries of recursive functions
+  /// reach a fixpoint.
+  fn widen(&mut self, max_derefs: usize) {
+    let mut widened = FlowSummary::default();
+    for (place, deps) in self.flows.drain(..) {
+      let place = match place {
+        SummaryPlace::Arg { index, projection }
+          if projection
+            .iter()
+            .filter(|elem| **elem == SummaryElem::Deref)
+            .count()
+            > max_derefs =>
+        {
+          SummaryPlace::ArgReachable { index }
+        }
+        place => place,
+      };
+      widened.join(&FlowSummary {
+        flows: vec![(place, deps)],
+      });
+    }
+    *self = widened;
+  }
+
+  /// Translates the summary to a call site `destination = f(args)` in `body`.
+  ///
+  /// Calls `f` with each place of the caller that is mutated by the call, along with
+  /// the places that flow into it.
+  pub fn apply<'tcx>(
+    &self,
+    aliases: &Aliases<'_, 'tcx>,
+    args: &[Operand<'tcx>],
+    destination: Option<Place<'tcx>>,
+    mut f: impl FnMut(
+      Place<'tcx>,
+      &[(Place<'tcx>, Option<&'tcx [PlaceElem<'tcx>]>)],
+      MutationStatus,
+    ),
+  ) {
+    let (tcx, body) = (aliases.tcx, aliases.body);
+    let param_env = tcx.param_env(aliases.def_id);
+    let arg_place = |index: usize| args.get(index).and_then(|arg| arg.to_place());
+    let arg_inputs = |indices: &[usize]| {
+      indices
+        .iter()
+        .filter_map(|index| Some((arg_place(*index)?, None)))
+        .collect::<Vec<_>>()
+    };
+
+    for (place, deps) in &self.flows {
+      match place {
+        SummaryPlace::Return => {
+          if let Some(dst) = destination {
+            let ret_is_unit = dst.ty(body.local_decls(), tcx).ty.is_unit();
+            let inputs = if ret_is_unit {
+              Vec::new()
+            } else {
+              arg_inputs(deps)
+            };
+            f(dst, &inputs, MutationStatus::Definitely);
+          }
+        }
+        SummaryPlace::Arg { index, projection } => {
+          // If the argument is a constant, then the callee can't mutate anything
+          // visible to the caller through it
+          if let Some(base) = arg_place(*index) {
+            let mutated = project(tcx, param_env, body, base, projection);
+            f(mutated, &arg_inputs(deps), MutationStatus::Possibly);
+          }
+        }
+        SummaryPlace::ArgReachable { index } => {
+          if let Some(base) = arg_place(*index) {
+            let inputs = arg_inputs(deps);
+            for mutated in aliases.reachable_values(base, Mutability::Mut) {
+              // As in ModularMutationVisitor, the argument itself is moved or copied
+              // into the callee, so it can't be mutated in a caller-visible way
+              if *mutated != base {
+                f(*mutated, &inputs, MutationStatus::Possibly);
+              }
+            }
+          }
+        }
+      }
+    }
+  }
+}
+
+/// Appends `projection` to `base`, computing the types of fields from the caller's
+/// view of `base`.
+///
+/// An unknown index has no corresponding place in the caller, so the projection stops
+/// at the indexed array or slice, i.e. every element is considered mutated. Likewise
+/// for a field of a type whose fields are unknown to the caller.
+fn project<'tcx>(
+  tcx: TyCtxt<'tcx>,
+  param_env: ParamEnv<'tcx>,
+  body: &Body<'tcx>,
+  base: Place<'tcx>,
+  projection: &[SummaryElem],
+) -> Place<'tcx> {
+  let mut ty = base.ty(body.local_decls(), tcx);
+  let mut elems = base.projection.to_vec();
+  for elem in projection {
+    let elem = match elem {
+      SummaryElem::Deref => ProjectionElem::Deref,
+      SummaryElem::Field(i) => {
+        let field = Field::from_usize(*i);
+        match utils::field_ty(tcx, ty, field) {
+          Some(field_ty) => ProjectionElem::Field(field, field_ty),
+          None => break,
+        }
+      }
+      SummaryElem::Index => break,
+      SummaryElem::ConstantIndex { offset, from_end } => ProjectionElem::ConstantIndex {
+        offset: *offset,
+        min_length: if *from_end { *offset } else { offset + 1 },
+        from_end: *from_end,
+      },
+      SummaryElem::Downcast(i) => {
+        ProjectionElem::Downcast(None, VariantIdx::from_usize(*i))
+      }
+    };
+    ty = ty.projection_ty_core(tcx, param_env, &elem, |_, _, field_ty| field_ty);
+    elems.push(elem);
+  }
+  Place::make(base.local, &elems, tcx)
+}
+
+// Summaries are keyed by the evaluation mode in which they were computed, since
+// e.g. a summary computed with ControlMode::DataOnly lacks the implicit flows
+type SummaryKey = (DefId, Option<EvalMode>);
+
+thread_local! {
+  static SUMMARIES: RefCell<HashMap<SummaryKey, Option<FlowSummary>>> =
+    RefCell::new(HashMap::default());
+}
+
+/// The maximum number of dereferences in a place of a recursive function's summary.
+const MAX_RECURSIVE_DEREFS: usize = 2;
+
+/// The maximum number of times the summaries of mutually recursive functions are
+/// recomputed before giving up on finding a fixpoint.
+const MAX_RECURSIVE_ITERATIONS: usize = 10;
+
+/// Returns the flow summary of the function `def_id`, computing it if necessary.
+///
+/// Summaries are computed bottom-up over the call graph of `def_id`. Mutually
+/// recursive functions are summarized together by starting from empty summaries and
+/// recomputing each function's summary until none of them change. Functions with a
+/// `flows` annotation (see [`models`]) are summarized by their annotation.
+///
+/// Returns `None` if the function cannot be summarized, e.g. if it is not defined
+/// in the local crate and has no exported summary.
+pub fn get_summary(tcx: TyCtxt<'_>, def_id: DefId) -> Option<FlowSummary> {
+  if let Some(flows) = models::annotated_flows(tcx, def_id) {
+    return Some(flows);
+  }
+
+  if let Some(summary) = cached_summary(def_id) {
+    return summary;
+  }
+
+  let local_def_id = match def_id.as_local() {
+    Some(local_def_id) => local_def_id,
+    None => return get_external_summary(tcx, def_id),
+  };
+  tcx.hir().get_if_local(def_id)?.body_id()?;
+
+  let call_graph = CallGraph::build(tcx, local_def_id);
+  for scc in call_graph.sccs() {
+    if scc.iter().all(|f| cached_summary(f.to_def_id()).is_some()) {
+      continue;
+    }
+
+    if call_graph.is_recursive(scc[0]) {
+      summarize_recursive(tcx, &scc);
+    } else {
+      // Cache a placeholder in case a recursive call was missed by the call graph
+      cache_summary(scc[0], None);
+      let summary = compute_summary(tcx, scc[0]);
+      cache_summary(scc[0], summary);
+    }
+  }
+
+  cached_summary(def_id).flatten()
+}
+
+fn cached_summary(def_id: DefId) -> Option<Option<FlowSummary>> {
+  let key = (def_id, EVAL_MODE.copied());
+  SUMMARIES.with(|summaries| summaries.borrow().get(&key).cloned())
+}
+
+fn cache_summary(def_id: LocalDefId, summary: Option<FlowSummary>) {
+  let key = (def_id.to_def_id(), EVAL_MODE.copied());
+  SUMMARIES.with(|summaries| summaries.borrow_mut().insert(key, summary));
+}
+
+fn summarize_recursive(tcx: TyCtxt<'_>, scc: &[LocalDefId]) {
+  info!("Summarizing recursive functions {scc:?}");
+  for def_id in scc {
+    cache_summary(*def_id, Some(FlowSummary::default()));
+  }
+
+  'fixpoint: for _ in 0 .. MAX_RECURSIVE_ITERATIONS {
+    let mut changed = false;
+    for def_id in scc {
+      let mut summary = match compute_summary(tcx, *def_id) {
+        Some(summary) => summary,
+        None => break 'fixpoint,
+      };
+      summary.widen(MAX_RECURSIVE_DEREFS);
+
+      let mut joined = cached_summary(def_id.to_def_id()).flatten().unwrap();
+      if joined.join(&summary) {
+        changed = true;
+        cache_summary(*def_id, Some(joined));
+      }
+    }
+
+    if !changed {
+      return;
+    }
+  }
+
+  // If a function in the SCC can't be summarized, or the summaries don't converge,
+  // then the callers of every function in the SCC fall back to the
+  // signature-based approximation
+  debug!("  Failed to summarize recursive functions");
+  for def_id in scc {
+    cache_summary(*def_id, None);
+  }
+}
+
+fn compute_summary(tcx: TyCtxt<'_>, def_id: LocalDefId) -> Option<FlowSummary> {
+  // If a function returns never (fn () -> !) then there are no exit points,
+  // so we can't analyze effects on exit
+  let fn_sig = tcx.fn_sig(def_id);
+  if fn_sig.skip_binder().output().is_never() {
+    debug!("  Func returns never");
+    return None;
+  }
+
+  let unsafety = tcx.unsafety_check_result(def_id);
+  if !unsafety.used_unsafe_blocks.is_empty() {
+    debug!("  Func contains unsafe blocks");
+    return None;
+  }
+
+  info!("Summarizing {}", tcx.def_path_debug_str(def_id.to_def_id()));
+  let hir_id = tcx.hir().local_def_id_to_hir_id(def_id);
+  let body_id = tcx.hir().body_owned_by(hir_id);
+  let body_with_facts = get_body_with_borrowck_facts(tcx, def_id);
+  let results =
+    super::compute_flow_internal::<TransitiveFlowDomain>(tcx, body_id, body_with_facts);
+  let summary = FlowSummary::from_results(body_with_facts.simplified_body(), &results);
+  debug!("  Summary: {summary:?}");
+
+  Some(summary)
+}
+
+impl<'tcx, D: FlowDomain<'tcx>> FlowAnalysis<'_, 'tcx, D> {
+  crate fn apply_call_summary(
+    &self,
+    state: &mut D,
+    call: &TerminatorKind<'tcx>,
+    location: Location,
+  ) -> bool {
+    let (func, args, destination) = match call {
+      TerminatorKind::Call {
+        func,
+        args,
+        destination,
+        ..
+      } => (func, args, destination),
+      _ => unreachable!(),
+    };
+    debug!("Checking whether can apply summary of {func:?}");
+
+    let def_id = match func.constant().map(|func| func.literal.ty().kind()) {
+      Some(TyKind::FnDef(def_id, _)) => *def_id,
+      _ => {
+        debug!("  Func is not a constant FnDef");
+        return false;
+      }
+    };
+
+    if self.any_closure_inputs(&utils::arg_places(args)) {
+      debug!("  Func has closure inputs");
+      return false;
+    }
+
+    let summary = match get_summary(self.tcx, def_id) {
+      Some(summary) => summary,
+      None => return false,
+    };
+
+    self.apply_summary(
+      state,
+      &summary,
+      args,
+      destination.map(|(dst, _)| dst),
+      location,
+    );
+
+    true
+  }
+
+  /// Applies the exported summary of a function from another crate, if one is
+  /// available. Exported summaries are used in every [`ContextMode`], since the bodies
+  /// of functions from other crates can't otherwise be analyzed.
+  ///
+  /// [`ContextMode`]: crate::extensions::ContextMode
+  crate fn apply_external_summary(
+    &self,
+    state: &mut D,
+    call: &TerminatorKind<'tcx>,
+    location: Location,
+  ) -> bool {
+    let (func, args, destination) = match call {
+      TerminatorKind::Call {
+        func,
+        args,
+        destination,
+        ..
+      } => (func, args, destination),
+      _ => unreachable!(),
+    };
+
+    let def_id = match utils::resolve_callee(self.tcx, self.def_id, func) {
+      Some((def_id, _)) if !def_id.is_local() => def_id,
+      _ => return false,
+    };
+
+    if self.any_closure_inputs(&utils::arg_places(args)) {
+      return false;
+    }
+
+    let summary = match get_external_summary(self.tcx, def_id) {
+      Some(summary) => summary,
+      None => return false,
+    };
+
+    debug!("Applying exported summary of {func:?}");
+    self.apply_summary(
+      state,
+      &summary,
+      args,
+      destination.map(|(dst, _)| dst),
+      location,
+    );
+
+    true
+  }
+
+  crate fn apply_summary(
+    &self,
+    state: &mut D,
+    summary: &FlowSummary,
+    args: &[Operand<'tcx>],
+    destination: Option<Place<'tcx>>,
+    location: Location,
+  ) {
+    summary.apply(
+      &self.aliases,
+      args,
+      destination,
+      |mutated, inputs, mutation_status| {
+        self.transfer_function(state, mutated, inputs, location, mutation_status)
+      },
+    );
+  }
+}
+
+fluid_let!(pub static SUMMARY_DIR: PathBuf);
+
+/// The version of the format of [`CrateSummaries`], which must be incremented whenever
+/// the format changes so that summaries written by older versions of Flowistry are ignored.
+pub const SUMMARY_VERSION: u32 = 2;
+
+/// The summaries of the public functions in a crate, as written by [`export_summaries`].
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct CrateSummaries {
+  /// The [`SUMMARY_VERSION`] of the Flowistry that wrote the summaries.
+  #[serde(default)]
+  pub version: u32,
+
+  /// The [`Svh`] of the summarized crate, which changes whenever the crate does.
+  ///
+  /// [`Svh`]: rustc_data_structures::svh::Svh
+  pub crate_hash: u64,
+
+  /// The summary of each function, keyed by the function's [`DefPathHash`] in hex.
+  ///
+  /// [`DefPathHash`]: rustc_span::def_id::DefPathHash
+  pub summaries: BTreeMap<String, FlowSummary>,
+}
+
+fn summary_key(tcx: TyCtxt<'_>, def_id: DefId) -> String {
+  tcx.def_path_hash(def_id).0.to_hex()
+}
+
+fn crate_summaries_path(dir: &Path, tcx: TyCtxt<'_>, krate: CrateNum) -> PathBuf {
+  let stable_crate_id = tcx.stable_crate_id(krate).to_u64();
+  dir.join(format!(
+    "{}-{stable_crate_id:016x}.json",
+    tcx.crate_name(krate)
+  ))
+}
+
+/// Summarizes every public function in the local crate that can be summarized,
+/// i.e. every function that can be called from another crate.
+pub fn summarize_crate(tcx: TyCtxt<'_>) -> CrateSummaries {
+  let access_levels = tcx.privacy_access_levels(());
+  let mut summaries = BTreeMap::new();
+  for def_id in tcx.hir().body_owners() {
+    if !matches!(tcx.def_kind(def_id), DefKind::Fn | DefKind::AssocFn)
+      || !access_levels.is_exported(def_id)
+    {
+      continue;
+    }
+
+    let def_id = def_id.to_def_id();
+    if let Some(summary) = get_summary(tcx, def_id) {
+      summaries.insert(summary_key(tcx, def_id), summary);
+    }
+  }
+  CrateSummaries {
+    version: SUMMARY_VERSION,
+    crate_hash: tcx.crate_hash(LOCAL_CRATE).as_u64(),
+    summaries,
+  }
+}
+
+/// Writes the summaries of the local crate to `dir`, where they are found by crates
+/// that depend on the local crate if `dir` is their [`SUMMARY_DIR`].
+pub fn export_summaries(tcx: TyCtxt<'_>, dir: &Path) -> Result<()> {
+  let summaries = summarize_crate(tcx);
+  info!(
+    "Exporting {} summaries for crate {}",
+    summaries.summaries.len(),
+    tcx.crate_name(LOCAL_CRATE)
+  );
+
+  fs::create_dir_all(dir)?;
+  let path = crate_summaries_path(dir, tcx, LOCAL_CRATE);
+  fs::write(path, serde_json::to_string(&summaries)?)?;
+  Ok(())
+}
+
+thread_local! {
+  static EXTERNAL_SUMMARIES: RefCell<HashMap<PathBuf, Option<Rc<CrateSummaries>>>> =
+    RefCell::new(HashMap::default());
+  static SUMMARY_STAMPS: RefCell<HashMap<PathBuf, Rc<Vec<SummaryStamp>>>> =
+    RefCell::new(HashMap::default());
+}
+
+/// Returns the exported summary of the function `def_id` from another crate,
+/// if the crate's summaries are in the current [`SUMMARY_DIR`].
+crate fn get_external_summary(tcx: TyCtxt<'_>, def_id: DefId) -> Option<FlowSummary> {
+  let dir = SUMMARY_DIR.cloned()?;
+  let path = crate_summaries_path(&dir, tcx, def_id.krate);
+  let crate_summaries = EXTERNAL_SUMMARIES.with(|external| {
+    external
+      .borrow_mut()
+      .entry(path)
+      .or_insert_with_key(|path| load_crate_summaries(path, tcx, def_id.krate))
+      .clone()
+  })?;
+  crate_summaries
+    .summaries
+    .get(&summary_key(tcx, def_id))
+    .cloned()
+}
+
+/// Identifies the contents of a file of exported summaries by the file's metadata,
+/// so the file does not have to be read to tell whether it changed.
+#[derive(Debug, Hash)]
+crate struct SummaryStamp {
+  path: PathBuf,
+  len: u64,
+  modified: Option<SystemTime>,
+}
+
+/// Returns a stamp of the exported summaries of each crate in the current
+/// [`SUMMARY_DIR`], which identifies the summaries available to the analysis.
+///
+/// The stamps are only computed once per session, since the summaries of the local
+/// crate's dependencies do not change while the local crate is compiled.
+crate fn external_summary_stamps(tcx: TyCtxt<'_>) -> Rc<Vec<SummaryStamp>> {
+  let dir = match SUMMARY_DIR.cloned() {
+    Some(dir) => dir,
+    None => return Rc::default(),
+  };
+  SUMMARY_STAMPS.with(|stamps| {
+    let mut stamps = stamps.borrow_mut();
+    let stamps = stamps.entry(dir).or_insert_with_key(|dir| {
+      let stamps = tcx
+        .crates(())
+        .iter()
+        .filter_map(|krate| {
+          let path = crate_summaries_path(dir, tcx, *krate);
+          let metadata = fs::metadata(&path).ok()?;
+          Some(SummaryStamp {
+            path,
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+          })
+        })
+        .collect();
+      Rc::new(stamps)
+    });
+    Rc::clone(stamps)
+  })
+}
+
+fn load_crate_summaries(
+  path: &Path,
+  tcx: TyCtxt<'_>,
+  krate: CrateNum,
+) -> Option<Rc<CrateSummaries>> {
+  let contents = fs::read_to_string(path).ok()?;
+  let summaries: CrateSummaries = match serde_json::from_str(&contents) {
+    Ok(summaries) => summaries,
+    Err(err) => {
+      warn!("Failed to load summaries from {}: {err}", path.display());
+      return None;
+    }
+  };
+
+  if summaries.version != SUMMARY_VERSION {
+    warn!(
+      "Summaries in {} have version {}, expected {SUMMARY_VERSION}",
+      path.display(),
+      summaries.version
+    );
+    return None;
+  }
+
+  if summaries.crate_hash != tcx.crate_hash(krate).as_u64() {
+    warn!("Summaries in {} are out of date", path.display());
+    return None;
+  }
+
+  Some(Rc::new(summaries))
+}
+
+#[cfg(test)]
+mod test {
+  use std::{env, process};
+
+  use rustc_hir::ItemKind;
+
+  use super::*;
+  use crate::{infoflow, mir::utils::BodyExt, test_utils};
+
+  /// Returns whether the result of `f(a, b)` in the crate `input` depends on `b`.
+  fn result_depends_on_b(input: &str, dir: &Path, summary_dir: Option<&Path>) -> bool {
+    let mut depends = false;
+    test_utils::compile_crate(input, "caller", dir, &[], |tcx| {
+      let hir = tcx.hir();
+      let body_id = hir
+        .items()
+        .find_map(|id| match hir.item(id).kind {
+          ItemKind::Fn(_, _, body) => Some(body),
+          _ => None,
+        })
+        .unwrap();
+      let def_id = hir.body_owner_def_id(body_id);
+      let body_with_facts = get_body_with_borrowck_facts(tcx, def_id);
+      let compute = || infoflow::compute_flow(tcx, body_id, body_with_facts);
+      let results = match summary_dir {
+        Some(summary_dir) => SUMMARY_DIR.set(summary_dir.to_path_buf(), compute),
+        None => compute(),
+      };
+
+      let body = body_with_facts.simplified_body();
+      let b = results
+        .analysis
+        .location_domain()
+        .arg_to_location(Local::from_usize(2));
+      depends = body.all_returns().any(|location| {
+        results
+          .state_at(location)
+          .row_set(Place::return_place())
+          .contains(b)
+      });
+    });
+    depends
+  }
+
+  const DEP: &str =
+    "pub fn copy(dst: &mut i32, src: &i32, _unused: &i32) { *dst = *src; }";
+
+  const CALLER: &str = r#"
+extern crate dep;
+pub fn f(a: i32, b: i32) -> i32 {
+  let mut x = 0;
+  dep::copy(&mut x, &a, &b);
+  x
+}
+"#;
+
+  /// Compiles [`DEP`] into a fresh directory and exports its summaries, returning the
+  /// directory and the path of the summary file.
+  fn export_dep(name: &str) -> (PathBuf, PathBuf) {
+    let dir = env::temp_dir().join(format!("flowistry-{name}-{}", process::id()));
+    let summary_dir = dir.join("summaries");
+    let mut path = PathBuf::new();
+    test_utils::compile_crate(DEP, "dep", &dir, &[], |tcx| {
+      export_summaries(tcx, &summary_dir).unwrap();
+      path = crate_summaries_path(&summary_dir, tcx, LOCAL_CRATE);
+    });
+    (dir, path)
+  }
+
+  #[test]
+  fn test_external_summaries() {
+    let (dir, path) = export_dep("summaries");
+    let summary_dir = path.parent().unwrap();
+    assert!(result_depends_on_b(CALLER, &dir, None));
+    assert!(!result_depends_on_b(CALLER, &dir, Some(summary_dir)));
+
+    fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn test_external_summaries_version() {
+    let (dir, path) = export_dep("summaries-version");
+    let summary_dir = path.parent().unwrap();
+    let summaries: CrateSummaries =
+      serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(summaries.version, SUMMARY_VERSION);
+    assert!(!summaries.summaries.is_empty());
+
+    // Summaries written by another version of Flowistry are ignored
+    let outdated = CrateSummaries {
+      version: SUMMARY_VERSION - 1,
+      ..summaries
+    };
+    fs::write(&path, serde_json::to_string(&outdated).unwrap()).unwrap();
+    assert!(result_depends_on_b(CALLER, &dir, Some(summary_dir)));
+
+    // As are summaries that predate versioning
+    let unversioned = serde_json::json!({
+      "crate_hash": outdated.crate_hash,
+      "summaries": outdated.summaries,
+    });
+    fs::write(&path, unversioned.to_string()).unwrap();
+    assert!(result_depends_on_b(CALLER, &dir, Some(summary_dir)));
+
+    fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn test_summary_subslice() {
+    let input = r#"
+fn set(a: &mut [i32; 3], x: i32) {
+  let [_, rest @ ..] = a;
+  let [b, _] = rest;
+  *b = x;
+}
+"#;
+    test_utils::compile(input, |tcx| {
+      let hir = tcx.hir();
+      let def_id = hir
+        .items()
+        .find(|id| matches!(hir.item(*id).kind, ItemKind::Fn(..)))
+        .unwrap()
+        .def_id;
+      let summary = get_summary(tcx, def_id.to_def_id()).unwrap();
+
+      // `*b` is `(*a)[1..3][0 of 2]`, i.e. `(*a)[1]` rather than `(*a)[0]`
+      let projections = summary
+        .flows
+        .iter()
+        .filter_map(|(place, _)| match place {
+          SummaryPlace::Arg {
+            index: 0,
+            projection,
+          } => Some(projection.as_slice()),
+          _ => None,
+        })
+        .collect::<Vec<_>>();
+      assert!(projections.contains(&[SummaryElem::Deref].as_slice()));
+      assert!(projections.iter().all(|projection| {
+        !projection.contains(&SummaryElem::ConstantIndex {
+          offset: 0,
+          from_end: false,
+        })
+      }));
+    });
+  }
+}
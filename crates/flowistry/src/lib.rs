@@ -51,6 +51,8 @@
 )]
 
 extern crate either;
+extern crate rustc_ast;
+extern crate rustc_ast_pretty;
 extern crate rustc_borrowck;
 extern crate rustc_data_structures;
 extern crate rustc_driver;
@@ -1,7 +1,7 @@
-use std::{hash::Hash, ops::ControlFlow, rc::Rc, time::Instant};
+use std::{borrow::Cow, hash::Hash, ops::ControlFlow, rc::Rc, time::Instant};
 
 use log::{debug, info};
-use rustc_borrowck::consumers::BodyWithBorrowckFacts;
+use rustc_borrowck::consumers::{PoloniusOutput, RichLocation};
 use rustc_data_structures::{
   fx::{FxHashMap as HashMap, FxHashSet as HashSet},
   graph::{iterate::reverse_post_order, scc::Sccs, vec_graph::VecGraph},
@@ -18,25 +18,26 @@ use rustc_middle::{
     *,
   },
   ty::{
-    Region, RegionKind, RegionVid, Ty, TyCtxt, TyKind, TypeAndMut, TypeFoldable,
-    TypeVisitor,
+    ParamEnv, Region, RegionKind, RegionVid, Ty, TyCtxt, TyKind, TypeAndMut,
+    TypeFoldable, TypeVisitor,
   },
 };
 
+use super::{
+  borrowck_facts::CachedSimplifedBodyWithFacts, globals::Globals, points_to::PointsTo,
+};
 use crate::{
   block_timer,
   cached::{Cache, CopyCache},
-  extensions::{is_extension_active, MutabilityMode, PointerMode},
+  extensions::{is_extension_active, MutabilityMode, PointerMode, EVAL_MODE},
   indexed::{
     impls::{LocationDomain, LocationIndex, LocationSet, PlaceSet},
     IndexMatrix, RefSet,
   },
-  mir::utils::{self, MutabilityExt, PlaceExt},
+  mir::utils::{self, MutabilityExt, PlaceExt, PlaceRelation},
   timer::elapsed,
 };
 
-use super::borrowck_facts::CachedSimplifedBodyWithFacts;
-
 #[derive(Default)]
 struct GatherBorrows<'tcx> {
   borrows: Vec<(RegionVid, BorrowKind, Place<'tcx>)>,
@@ -101,23 +102,33 @@ impl<'tcx> Visitor<'tcx> for FindPlaces<'_, 'tcx> {
     if let Rvalue::Aggregate(box agg_k, _) = rvalue {
       let places = match agg_k {
         AggregateKind::Adt(def_id, idx, substs, _, _) => {
-          let adt_def = self.tcx.adt_def(*def_id);
+          let tcx = self.tcx;
+          let adt_def = tcx.adt_def(*def_id);
           let variant = adt_def.variant(*idx);
-          let places = variant.fields.iter().enumerate().map(|(i, field)| {
+          let downcast = adt_def
+            .is_enum()
+            .then(|| ProjectionElem::Downcast(Some(variant.name), *idx));
+          let places = variant.fields.iter().enumerate().map(move |(i, field)| {
             let mut projection = place.projection.to_vec();
+            projection.extend(downcast);
             projection.push(ProjectionElem::Field(
               Field::from_usize(i),
-              field.ty(self.tcx, substs),
+              field.ty(tcx, substs),
             ));
-            Place::make(place.local, &projection, self.tcx)
+            Place::make(place.local, &projection, tcx)
           });
           Box::new(places) as Box<dyn Iterator<Item=Place<'tcx>>>
         }
-        AggregateKind::Generator(def_id, _, _) => {
-          let gen_def = self.tcx.generator_layout(*def_id).unwrap();
-          let variant = gen_def.variant_fields.raw.first().unwrap();
-          let places = variant.iter_enumerated().map(|(field, saved_local)| {
-            place.project_deeper(&[ProjectionElem::Field(field, gen_def.field_tys[*saved_local])], self.tcx)
+        // The operands of a generator are its upvars, which are the fields of the
+        // generator before it is first resumed. The locals that are saved across
+        // yields (its `generator_layout`) are not needed, since bodies are analyzed
+        // before the generator transform, where saved locals are still ordinary
+        // locals that are tracked individually across each yield.
+        AggregateKind::Generator(_, substs, _) => {
+          let tcx = self.tcx;
+          let upvar_tys = substs.as_generator().upvar_tys();
+          let places = upvar_tys.enumerate().map(move |(i, ty)| {
+            place.project_deeper(&[ProjectionElem::Field(Field::from_usize(i), ty)], tcx)
           });
           Box::new(places) as Box<_>
         }
@@ -145,8 +156,49 @@ impl<'tcx> Visitor<'tcx> for FindPlaces<'_, 'tcx> {
   }
 }
 
-type LoanSet<'tcx> = HashSet<(Place<'tcx>, Mutability)>;
-type LoanMap<'tcx> = HashMap<RegionVid, LoanSet<'tcx>>;
+struct FindConstantIndices<'tcx> {
+  tcx: TyCtxt<'tcx>,
+  param_env: ParamEnv<'tcx>,
+  constants: HashMap<Local, u64>,
+  mutations: HashMap<Local, usize>,
+}
+
+impl<'tcx> Visitor<'tcx> for FindConstantIndices<'tcx> {
+  fn visit_assign(
+    &mut self,
+    place: &Place<'tcx>,
+    rvalue: &Rvalue<'tcx>,
+    location: Location,
+  ) {
+    if let (Some(local), Rvalue::Use(Operand::Constant(constant))) =
+      (place.as_local(), rvalue)
+    {
+      if constant.ty() == self.tcx.types.usize {
+        if let Some(value) = constant.literal.try_eval_usize(self.tcx, self.param_env) {
+          self.constants.insert(local, value);
+        }
+      }
+    }
+    self.super_assign(place, rvalue, location);
+  }
+
+  fn visit_local(&mut self, local: &Local, context: PlaceContext, _location: Location) {
+    if context.is_mutating_use() {
+      *self.mutations.entry(*local).or_default() += 1;
+    }
+  }
+}
+
+crate type LoanSet<'tcx> = HashSet<(Place<'tcx>, Mutability)>;
+crate type LoanMap<'tcx> = HashMap<RegionVid, LoanSet<'tcx>>;
+crate type LiveLoanMap<'tcx> = HashMap<Location, Vec<Place<'tcx>>>;
+
+/// The places borrowed by the loans live at each location, along with every place
+/// borrowed by some loan, i.e. the places whose liveness is known.
+crate struct LiveLoans<'tcx> {
+  at: LiveLoanMap<'tcx>,
+  borrowed: HashSet<Place<'tcx>>,
+}
 
 pub const UNKNOWN_REGION: RegionVid = RegionVid::MAX;
 
@@ -160,10 +212,24 @@ pub struct Aliases<'a, 'tcx> {
   // Core computed data structure
   loans: LoanMap<'tcx>,
 
+  // The places borrowed by the loans live at each location, if aliases are
+  // computed flow-sensitively
+  live_loans: Option<LiveLoans<'tcx>>,
+
+  // The targets of raw pointers and boxes, if they are tracked
+  points_to: Option<PointsTo<'tcx>>,
+
+  // The locals that are only ever assigned a constant index
+  constant_indices: HashMap<Local, u64>,
+
+  // The mutable globals accessed by the body or its callees
+  globals: Globals<'tcx>,
+
   // Caching for derived analysis
   normalized_cache: CopyCache<Place<'tcx>, Place<'tcx>>,
   aliases_cache: Cache<Place<'tcx>, PlaceSet<'tcx>>,
   conflicts_cache: Cache<Place<'tcx>, PlaceSet<'tcx>>,
+  indexed_places_cache: Cache<(), Vec<Place<'tcx>>>,
   reachable_cache: Cache<(Place<'tcx>, Mutability), PlaceSet<'tcx>>,
 }
 
@@ -187,8 +253,9 @@ impl<'a, 'tcx> Aliases<'a, 'tcx> {
     let body = &body_with_facts.simplified_body();
     let static_region = RegionVid::from_usize(0);
     // Core set of outlives constraints (points interpretation)
-    let ref subset_base = 
-      body_with_facts.input_facts().subset_base
+    let subset_base = &body_with_facts
+      .input_facts()
+      .subset_base
       .iter()
       .cloned()
       .filter(|(r1, r2, i)| constraint_selector(*r1, *r2, *i))
@@ -287,13 +354,7 @@ impl<'a, 'tcx> Aliases<'a, 'tcx> {
       {
         let region_contains = contains.entry(region).or_default();
         for (place, mutability) in places {
-          // WARNING / TODO: this is a huge hack (that is conjoined w/ all_args).
-          // Need a way to limit the number of possible pointers for functions with
-          // many pointers in the input. This is almost certainly not sound, but hopefully
-          // it works for most cases.
-          if place.projection.len() <= 2 {
-            region_contains.insert((tcx.mk_place_deref(place), mutability));
-          }
+          region_contains.insert((arg_pointee(tcx, place), mutability));
         }
       }
     }
@@ -441,8 +502,109 @@ impl<'a, 'tcx> Aliases<'a, 'tcx> {
     let loans = Self::compute_loans(tcx, def_id, body_with_facts, fact_selector);
     debug!("Loans: {loans:?}");
 
+    let mut aliases = Self::from_loans(tcx, def_id, body, location_domain, loans);
+    if is_extension_active(|mode| mode.pointer_mode == PointerMode::FlowSensitive) {
+      aliases.live_loans = Some(Self::compute_live_loans(body_with_facts));
+    }
+    if is_extension_active(|mode| mode.pointer_mode == PointerMode::PointsTo) {
+      aliases.points_to = Some(PointsTo::build(tcx, def_id, body, &aliases.loans));
+    }
+    aliases
+  }
+
+  /// Computes the places borrowed by the loans that are live at each location of
+  /// the simplified body, using Polonius' loan liveness.
+  fn compute_live_loans(
+    body_with_facts: &CachedSimplifedBodyWithFacts<'tcx>,
+  ) -> LiveLoans<'tcx> {
+    let start = Instant::now();
+    let borrowckd_body = body_with_facts.borrowckd_body();
+    let input_facts = body_with_facts.input_facts();
+    let location_table = body_with_facts.location_table();
+
+    // The output facts computed by rustc are location-insensitive for bodies
+    // without borrow errors, so loan liveness is computed here. Polonius only
+    // records `loan_live_at` if dumping is enabled.
+    let output =
+      PoloniusOutput::compute(input_facts, polonius_engine::Algorithm::DatafrogOpt, true);
+
+    let borrowed_places = input_facts
+      .loan_issued_at
+      .iter()
+      .filter_map(|(_, loan, point)| {
+        let location = match location_table.to_location(*point) {
+          RichLocation::Start(location) | RichLocation::Mid(location) => location,
+        };
+        match &borrowckd_body.stmt_at(location).left()?.kind {
+          StatementKind::Assign(box (_, Rvalue::Ref(_, _, place))) => {
+            Some((*loan, *place))
+          }
+          _ => None,
+        }
+      })
+      .collect::<HashMap<_, _>>();
+
+    // The simplified body only lacks the StorageLive/StorageDead statements of
+    // the borrow-checked body, so its locations are mapped back by skipping them
+    let mut live_loans = LiveLoanMap::default();
+    for (block, data) in borrowckd_body.basic_blocks().iter_enumerated() {
+      let retained = data
+        .statements
+        .iter()
+        .enumerate()
+        .filter(|(_, stmt)| {
+          !matches!(
+            stmt.kind,
+            StatementKind::StorageLive(..) | StatementKind::StorageDead(..)
+          )
+        })
+        .map(|(i, _)| i)
+        .chain([data.statements.len()]);
+      for (statement_index, original_index) in retained.enumerate() {
+        let original = Location {
+          block,
+          statement_index: original_index,
+        };
+        let points = [
+          location_table.start_index(original),
+          location_table.mid_index(original),
+        ];
+        let places = points
+          .iter()
+          .filter_map(|point| output.loan_live_at.get(point))
+          .flatten()
+          .filter_map(|loan| borrowed_places.get(loan).copied())
+          .collect::<HashSet<_>>();
+        let location = Location {
+          block,
+          statement_index,
+        };
+        live_loans.insert(location, places.into_iter().collect());
+      }
+    }
+    elapsed("loan liveness", start);
+
+    LiveLoans {
+      at: live_loans,
+      borrowed: borrowed_places.into_values().collect(),
+    }
+  }
+
+  /// Creates an alias analysis from previously computed loans, e.g. from
+  /// [`Aliases::loans`] of an earlier analysis of the same body.
+  crate fn from_loans(
+    tcx: TyCtxt<'tcx>,
+    def_id: DefId,
+    body: &'a Body<'tcx>,
+    location_domain: Rc<LocationDomain>,
+    loans: LoanMap<'tcx>,
+  ) -> Self {
     Aliases {
       loans,
+      live_loans: None,
+      points_to: None,
+      constant_indices: Self::compute_constant_indices(tcx, def_id, body),
+      globals: Globals::build(tcx, body),
       tcx,
       body,
       def_id,
@@ -450,14 +612,68 @@ impl<'a, 'tcx> Aliases<'a, 'tcx> {
       aliases_cache: Cache::default(),
       normalized_cache: CopyCache::default(),
       conflicts_cache: Cache::default(),
+      indexed_places_cache: Cache::default(),
       reachable_cache: Cache::default(),
     }
   }
 
+  /// Finds the locals which are assigned a constant exactly once, so that an index
+  /// by such a local (e.g. the temporary of `a[1]`) can be treated as a constant index.
+  fn compute_constant_indices(
+    tcx: TyCtxt<'tcx>,
+    def_id: DefId,
+    body: &Body<'tcx>,
+  ) -> HashMap<Local, u64> {
+    let mut finder = FindConstantIndices {
+      tcx,
+      param_env: tcx.param_env(def_id),
+      constants: HashMap::default(),
+      mutations: HashMap::default(),
+    };
+    finder.visit_body(body);
+
+    // The return place is excluded since normalized places use Index(_0) for an
+    // unknown index, see PlaceExt::normalize
+    finder
+      .constants
+      .into_iter()
+      .filter(|(local, _)| {
+        *local != RETURN_PLACE && finder.mutations.get(local).copied() == Some(1)
+      })
+      .collect()
+  }
+
+  crate fn loans(&self) -> &LoanMap<'tcx> {
+    &self.loans
+  }
+
+  pub fn globals(&self) -> &Globals<'tcx> {
+    &self.globals
+  }
+
   pub fn normalize(&self, place: Place<'tcx>) -> Place<'tcx> {
-    self
-      .normalized_cache
-      .get(place, |place| place.normalize(self.tcx, self.def_id))
+    self.normalized_cache.get(place, |place| {
+      let projection = place
+        .projection
+        .iter()
+        .map(|elem| match elem {
+          ProjectionElem::Index(local) => match self.constant_indices.get(&local) {
+            Some(offset) => ProjectionElem::ConstantIndex {
+              offset: *offset,
+              min_length: offset + 1,
+              from_end: false,
+            },
+            None => elem,
+          },
+          _ => elem,
+        })
+        .collect::<Vec<_>>();
+      let place = Place::make(place.local, &projection, self.tcx);
+      self
+        .globals
+        .normalize(place, self.tcx)
+        .normalize(self.tcx, self.def_id)
+    })
   }
 
   pub fn aliases(&self, place: Place<'tcx>) -> &PlaceSet<'tcx> {
@@ -488,16 +704,29 @@ impl<'a, 'tcx> Aliases<'a, 'tcx> {
         }
       };
 
+      // If the targets of raw pointers and boxes are tracked, then use those
+      // instead of the loans of the unknown region
+      let region_loans = match &self.points_to {
+        Some(points_to) if region == UNKNOWN_REGION => {
+          let ptr = self.normalize(Place::from_ref(ptr, self.tcx));
+          points_to
+            .targets(ptr.collapse_indices(self.tcx))
+            .iter()
+            .collect::<Vec<_>>()
+        }
+        _ => self
+          .loans
+          .get(&region)
+          .into_iter()
+          .flatten()
+          .map(|(loan, _)| loan)
+          .collect::<Vec<_>>(),
+      };
+
       // For each p ∈ loans('region),
       //   if p : orig_ty then add: after[p]
       //   else add: p
-      let region_loans = self
-        .loans
-        .get(&region)
-        .map(|loans| loans.iter())
-        .into_iter()
-        .flatten();
-      let region_aliases = region_loans.map(|(loan, _)| {
+      let region_aliases = region_loans.into_iter().map(|loan| {
         let loan_ty = loan.ty(self.body.local_decls(), self.tcx).ty;
         if orig_ty == loan_ty {
           let mut projection = loan.projection.to_vec();
@@ -514,8 +743,86 @@ impl<'a, 'tcx> Aliases<'a, 'tcx> {
     })
   }
 
+  /// Returns true if `alias` may be an alias at `location`, i.e. if `alias` is not
+  /// a direct place or if a loan of a place overlapping `alias` is live at `location`.
+  ///
+  /// Only the liveness of references is known, so an alias that is not borrowed by
+  /// any reference (e.g. an alias from `ptr::addr_of_mut!`) is always live.
+  fn is_live_at(&self, alias: Place<'tcx>, location: Location) -> bool {
+    let live_loans = match &self.live_loans {
+      Some(live_loans) => live_loans,
+      None => return true,
+    };
+    let overlaps = |borrowed: &Place<'tcx>| {
+      borrowed.local == alias.local
+        && (borrowed.projection.starts_with(alias.projection)
+          || alias.projection.starts_with(borrowed.projection))
+    };
+    alias.is_indirect()
+      || !live_loans.borrowed.iter().any(overlaps)
+      || live_loans
+        .at
+        .get(&location)
+        .into_iter()
+        .flatten()
+        .any(overlaps)
+  }
+
+  /// Returns true if `place` is accessed through a raw pointer, whose aliases outlive
+  /// the loans they were created from.
+  fn is_through_raw_ptr(&self, place: Place<'tcx>) -> bool {
+    place.iter_projections().any(|(base, elem)| {
+      elem == ProjectionElem::Deref
+        && base
+          .ty(self.body.local_decls(), self.tcx)
+          .ty
+          .is_unsafe_ptr()
+    })
+  }
+
+  fn filter_live_at<'b>(
+    &self,
+    place: Place<'tcx>,
+    places: &'b PlaceSet<'tcx>,
+    location: Location,
+  ) -> Cow<'b, PlaceSet<'tcx>> {
+    if self.live_loans.is_none() || self.is_through_raw_ptr(place) {
+      return Cow::Borrowed(places);
+    }
+    Cow::Owned(
+      places
+        .iter()
+        .filter(|alias| **alias == place || self.is_live_at(**alias, location))
+        .copied()
+        .collect(),
+    )
+  }
+
+  /// Like [`Aliases::aliases`], but only includes the aliases of `place` whose
+  /// loans are live at `location` if the pointer mode is flow-sensitive.
+  pub fn aliases_at(
+    &self,
+    place: Place<'tcx>,
+    location: Location,
+  ) -> Cow<'_, PlaceSet<'tcx>> {
+    self.filter_live_at(place, self.aliases(place), location)
+  }
+
   pub fn children(&self, place: Place<'tcx>) -> PlaceSet<'tcx> {
-    HashSet::from_iter(place.interior_places(self.tcx, self.body, self.def_id))
+    // An opaque global has no type in the body, so it has no children but itself
+    if self.globals.is_opaque(place) {
+      return HashSet::from_iter([place]);
+    }
+
+    // A global is not part of a pointer to the global, since assigning the pointer
+    // does not change the global
+    let in_global = self.globals.is_global(place);
+    HashSet::from_iter(
+      place
+        .interior_places(self.tcx, self.body, self.def_id)
+        .into_iter()
+        .filter(|child| in_global || !self.globals.is_global(*child)),
+    )
   }
 
   pub fn conflicts(&self, place: Place<'tcx>) -> &PlaceSet<'tcx> {
@@ -523,18 +830,108 @@ impl<'a, 'tcx> Aliases<'a, 'tcx> {
       self
         .aliases(place)
         .iter()
-        .flat_map(|alias| {
-          let children = self.children(*alias);
-          let parents = alias
+        .flat_map(|alias| self.alias_conflicts(*alias))
+        .collect()
+    })
+  }
+
+  fn alias_conflicts(
+    &self,
+    alias: Place<'tcx>,
+  ) -> impl Iterator<Item = Place<'tcx>> + '_ {
+    let tcx = self.tcx;
+    let children = self.children(alias);
+
+    // A place within an enum variant does not conflict with the enum itself, whose
+    // discriminant is unaffected. See Aliases::reachable_values for the converse.
+    let parents = alias
+      .iter_projections()
+      .take_while(|(_, elem)| !matches!(elem, PlaceElem::Deref))
+      .filter(move |(place_ref, _)| {
+        !alias.projection[place_ref.projection.len() ..]
+          .iter()
+          .any(|elem| matches!(elem, PlaceElem::Downcast(..)))
+      })
+      .map(move |(place_ref, _)| Place::from_ref(place_ref, tcx));
+
+    // A place within an element of an array also conflicts with the same place
+    // within an unknown element, and with any indexed place that may overlap it,
+    // e.g. a[0] conflicts with a[_] and a[0..2] but not a[1].
+    let alias = self.normalize(alias);
+    let overlapping = self
+      .indexed_places()
+      .iter()
+      .filter(move |indexed| {
+        PlaceRelation::configurable_of(**indexed, alias, false).overlaps()
+      })
+      .copied();
+
+    children
+      .into_iter()
+      .chain(parents)
+      .flat_map(move |place| [place, place.collapse_indices(tcx)])
+      .chain(overlapping)
+  }
+
+  /// Returns the normalized places with a constant index or subslice that may be
+  /// accessed in the body, either directly or through an alias.
+  fn indexed_places(&self) -> &Vec<Place<'tcx>> {
+    self.indexed_places_cache.get((), |_| {
+      let mut finder = FindPlaces {
+        tcx: self.tcx,
+        body: self.body,
+        def_id: self.def_id,
+        places: Vec::new(),
+      };
+      finder.visit_body(self.body);
+
+      let places = finder
+        .places
+        .into_iter()
+        .flat_map(|place| {
+          place
             .iter_projections()
-            .take_while(|(_, elem)| !matches!(elem, PlaceElem::Deref))
-            .map(|(place_ref, _)| Place::from_ref(place_ref, self.tcx));
-          children.into_iter().chain(parents)
+            .map(|(place_ref, _)| Place::from_ref(place_ref, self.tcx))
+            .chain([place])
         })
+        .collect::<HashSet<_>>();
+
+      places
+        .into_iter()
+        .flat_map(|place| self.aliases(place).iter().copied())
+        .map(|place| self.normalize(place))
+        .filter(|place| {
+          place.projection.iter().any(|elem| {
+            matches!(
+              elem,
+              ProjectionElem::ConstantIndex { .. } | ProjectionElem::Subslice { .. }
+            )
+          })
+        })
+        .collect::<HashSet<_>>()
+        .into_iter()
         .collect()
     })
   }
 
+  /// Like [`Aliases::conflicts`], but computed from [`Aliases::aliases_at`].
+  pub fn conflicts_at(
+    &self,
+    place: Place<'tcx>,
+    location: Location,
+  ) -> Cow<'_, PlaceSet<'tcx>> {
+    if self.live_loans.is_none() {
+      return Cow::Borrowed(self.conflicts(place));
+    }
+    Cow::Owned(
+      self
+        .aliases_at(place, location)
+        .iter()
+        .flat_map(|alias| self.alias_conflicts(*alias))
+        .collect(),
+    )
+  }
+
   fn collect_loans(&self, ty: Ty<'tcx>, mutability: Mutability) -> PlaceSet<'tcx> {
     let mut collector = LoanCollector {
       aliases: self,
@@ -553,13 +950,37 @@ impl<'a, 'tcx> Aliases<'a, 'tcx> {
     mutability: Mutability,
   ) -> &PlaceSet<'tcx> {
     self.reachable_cache.get((place, mutability), |_| {
+      if self.globals.is_opaque(place) {
+        return HashSet::from_iter([place]);
+      }
+
       let ty = place.ty(self.body.local_decls(), self.tcx).ty;
       let loans = self.collect_loans(ty, mutability);
+
+      // If the targets of raw pointers and boxes are tracked, then the values
+      // reachable from those pointers are their targets (see LoanCollector)
+      let pointees = match &self.points_to {
+        Some(_) => place
+          .interior_pointers(self.tcx, self.body, self.def_id)
+          .remove(&UNKNOWN_REGION)
+          .unwrap_or_default()
+          .into_iter()
+          .map(|(ptr, _)| self.tcx.mk_place_deref(ptr))
+          .collect::<Vec<_>>(),
+        None => Vec::new(),
+      };
+
+      // Mutations of enum variants are not propagated to the enum itself (see
+      // Aliases::alias_conflicts), so the values of an enum include its variants
       loans
         .into_iter()
+        .chain(pointees)
         .chain([place])
         .flat_map(|place| self.aliases(place).iter().copied())
         .filter(|place| {
+          if self.globals.is_global(*place) {
+            return true;
+          }
           if let Some((place, _)) = place.refs_in_projection().last() {
             let ty = place.ty(self.body.local_decls(), self.tcx).ty;
             if ty.is_box() || ty.is_unsafe_ptr() {
@@ -568,10 +989,29 @@ impl<'a, 'tcx> Aliases<'a, 'tcx> {
           }
           place.is_direct(self.body)
         })
+        .flat_map(|value| {
+          let variants = self.children(value).into_iter().filter(move |child| {
+            child.projection[value.projection.len() ..]
+              .iter()
+              .any(|elem| matches!(elem, PlaceElem::Downcast(..)))
+          });
+          variants.chain([value])
+        })
         .collect()
     })
   }
 
+  /// Like [`Aliases::reachable_values`], but only includes the values whose loans
+  /// are live at `location` if the pointer mode is flow-sensitive.
+  pub fn reachable_values_at(
+    &self,
+    place: Place<'tcx>,
+    mutability: Mutability,
+    location: Location,
+  ) -> Cow<'_, PlaceSet<'tcx>> {
+    self.filter_live_at(place, self.reachable_values(place, mutability), location)
+  }
+
   pub fn all_args(&'a self) -> impl Iterator<Item = (Place<'tcx>, LocationIndex)> + 'a {
     self.body.args_iter().flat_map(|local| {
       let location = self.location_domain().arg_to_location(local);
@@ -579,12 +1019,7 @@ impl<'a, 'tcx> Aliases<'a, 'tcx> {
       let ptrs = place
         .interior_pointers(self.tcx, self.body, self.def_id)
         .into_values()
-        .flat_map(|ptrs| {
-          ptrs
-            .into_iter()
-            .filter(|(ptr, _)| ptr.projection.len() <= 2)
-            .map(|(ptr, _)| self.tcx.mk_place_deref(ptr))
-        });
+        .flat_map(|ptrs| ptrs.into_iter().map(|(ptr, _)| arg_pointee(self.tcx, ptr)));
       ptrs
         .chain([place])
         .flat_map(|place| place.interior_places(self.tcx, self.body, self.def_id))
@@ -605,6 +1040,22 @@ impl<'a, 'tcx> Aliases<'a, 'tcx> {
   }
 }
 
+/// Returns the place standing for `*ptr`, where `ptr` is a pointer within an argument.
+///
+/// To bound the number of places for functions with deeply nested pointers in their
+/// arguments, pointers deeper than the depth limit of the
+/// [`EvalMode`](crate::extensions::EvalMode) are k-limited: `*ptr` is summarized by
+/// the prefix of `ptr` at the depth limit. Accesses through `ptr` are then (soundly,
+/// but imprecisely) treated as accesses to that prefix.
+fn arg_pointee<'tcx>(tcx: TyCtxt<'tcx>, ptr: Place<'tcx>) -> Place<'tcx> {
+  let depth_limit = EVAL_MODE.copied().unwrap_or_default().depth_limit;
+  if ptr.projection.len() <= depth_limit {
+    tcx.mk_place_deref(ptr)
+  } else {
+    Place::make(ptr.local, &ptr.projection[.. depth_limit], tcx)
+  }
+}
+
 pub fn generate_conservative_constraints<'tcx>(
   tcx: TyCtxt<'tcx>,
   body: &Body<'tcx>,
@@ -653,8 +1104,14 @@ impl<'tcx> TypeVisitor<'tcx> for LoanCollector<'_, 'tcx> {
 
   fn visit_ty(&mut self, ty: Ty<'tcx>) -> ControlFlow<Self::BreakTy> {
     match ty.kind() {
-      TyKind::Ref(_, _, mutability) => {
-        self.stack.push(*mutability);
+      TyKind::Ref(_, pointee, mutability) => {
+        let (tcx, def_id) = (self.aliases.tcx, self.aliases.def_id);
+        let mutability = if utils::is_interior_mutable(tcx, def_id, *pointee) {
+          Mutability::Mut
+        } else {
+          *mutability
+        };
+        self.stack.push(mutability);
         ty.super_visit_with(self);
         self.stack.pop();
         return ControlFlow::Break(());
@@ -682,10 +1139,18 @@ impl<'tcx> TypeVisitor<'tcx> for LoanCollector<'_, 'tcx> {
       }
       _ => unreachable!("{region:?}"),
     };
+    if region == UNKNOWN_REGION && self.aliases.points_to.is_some() {
+      return ControlFlow::Continue(());
+    }
     if let Some(loans) = self.aliases.loans.get(&region) {
       let under_immut_ref = self.stack.iter().any(|m| *m == Mutability::Not);
       let ignore_mut =
         is_extension_active(|mode| mode.mutability_mode == MutabilityMode::IgnoreMut);
+      let aliases = self.aliases;
+      let is_interior_mutable = |place: Place<'tcx>| {
+        let ty = place.ty(aliases.body.local_decls(), aliases.tcx).ty;
+        utils::is_interior_mutable(aliases.tcx, aliases.def_id, ty)
+      };
       self
         .loans
         .extend(loans.iter().filter_map(|(place, mutability)| {
@@ -694,6 +1159,8 @@ impl<'tcx> TypeVisitor<'tcx> for LoanCollector<'_, 'tcx> {
           }
           let loan_mutability = if under_immut_ref {
             Mutability::Not
+          } else if is_interior_mutable(*place) {
+            Mutability::Mut
           } else {
             *mutability
           };
@@ -725,7 +1192,7 @@ mod test {
     }
     "#;
     test_utils::compile_body(input, |tcx, body_id, body_with_facts| {
-      let body = &body_with_facts.body;
+      let body = body_with_facts.simplified_body();
       let def_id = tcx.hir().body_owner_def_id(body_id);
       let aliases = Aliases::build(tcx, def_id.to_def_id(), body_with_facts);
       let name_map = body
@@ -0,0 +1,127 @@
+//! The call graph of the functions in the local crate.
+
+use rustc_data_structures::{
+  fx::FxHashMap as HashMap,
+  graph::{scc::Sccs, vec_graph::VecGraph},
+};
+use rustc_hir::def_id::LocalDefId;
+use rustc_index::vec::IndexVec;
+use rustc_middle::{
+  mir::*,
+  ty::{TyCtxt, TyKind},
+};
+
+use super::borrowck_facts::get_body_with_borrowck_facts;
+
+rustc_index::newtype_index! {
+  pub struct FunctionIndex {
+      DEBUG_FORMAT = "fn{}"
+  }
+}
+
+rustc_index::newtype_index! {
+  pub struct FunctionSccIndex {
+      DEBUG_FORMAT = "fs{}"
+  }
+}
+
+/// The functions transitively called by a root function, grouped into
+/// strongly connected components (i.e. sets of mutually recursive functions).
+///
+/// Only direct calls to functions with a body in the local crate are included.
+pub struct CallGraph {
+  functions: IndexVec<FunctionIndex, LocalDefId>,
+  indices: HashMap<LocalDefId, FunctionIndex>,
+  sccs: Sccs<FunctionIndex, FunctionSccIndex>,
+  self_calls: Vec<FunctionIndex>,
+}
+
+impl CallGraph {
+  pub fn build(tcx: TyCtxt<'_>, root: LocalDefId) -> Self {
+    let mut functions = IndexVec::new();
+    let mut indices = HashMap::default();
+    let mut edge_pairs = Vec::new();
+    let mut stack = vec![root];
+    indices.insert(root, functions.push(root));
+
+    while let Some(caller) = stack.pop() {
+      let caller_index = indices[&caller];
+      for callee in callees(tcx, caller) {
+        let callee_index = *indices.entry(callee).or_insert_with(|| {
+          stack.push(callee);
+          functions.push(callee)
+        });
+        edge_pairs.push((caller_index, callee_index));
+      }
+    }
+
+    let self_calls = edge_pairs
+      .iter()
+      .filter(|(caller, callee)| caller == callee)
+      .map(|(caller, _)| *caller)
+      .collect();
+    let graph = VecGraph::new(functions.len(), edge_pairs);
+    let sccs = Sccs::new(&graph);
+
+    CallGraph {
+      functions,
+      indices,
+      sccs,
+      self_calls,
+    }
+  }
+
+  /// Returns each strongly connected component of the call graph, such that
+  /// the callees of a component come before the component itself.
+  pub fn sccs(&self) -> Vec<Vec<LocalDefId>> {
+    let mut sccs = vec![Vec::new(); self.sccs.num_sccs()];
+    for (index, def_id) in self.functions.iter_enumerated() {
+      sccs[self.sccs.scc(index).index()].push(*def_id);
+    }
+    sccs
+  }
+
+  /// Returns true if the functions in the component of `def_id` call each other,
+  /// i.e. if `def_id` is recursive.
+  pub fn is_recursive(&self, def_id: LocalDefId) -> bool {
+    let index = match self.indices.get(&def_id) {
+      Some(index) => *index,
+      None => return false,
+    };
+    let scc = self.sccs.scc(index);
+    self.self_calls.contains(&index)
+      || self
+        .functions
+        .indices()
+        .any(|other| other != index && self.sccs.scc(other) == scc)
+  }
+}
+
+/// The local functions with a body that are called directly by `def_id`.
+fn callees(tcx: TyCtxt<'_>, def_id: LocalDefId) -> Vec<LocalDefId> {
+  let body_with_facts = get_body_with_borrowck_facts(tcx, def_id);
+  let body = body_with_facts.simplified_body();
+  body
+    .basic_blocks()
+    .iter()
+    .filter_map(|data| local_callee(tcx, &data.terminator().kind))
+    .collect()
+}
+
+/// The local function with a body that is called directly by `kind`, if any.
+pub fn local_callee(tcx: TyCtxt<'_>, kind: &TerminatorKind<'_>) -> Option<LocalDefId> {
+  match kind {
+    TerminatorKind::Call { func, .. } => {
+      match func.constant().map(|func| func.literal.ty().kind()) {
+        Some(TyKind::FnDef(callee, _)) => {
+          let callee = callee.as_local()?;
+          let hir_id = tcx.hir().local_def_id_to_hir_id(callee);
+          tcx.hir().maybe_body_owned_by(hir_id)?;
+          Some(callee)
+        }
+        _ => None,
+      }
+    }
+    _ => None,
+  }
+}
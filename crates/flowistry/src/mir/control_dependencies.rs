@@ -4,30 +4,45 @@ use rustc_data_structures::{
   fx::FxHashMap as HashMap,
   graph::{vec_graph::VecGraph, *},
 };
-use rustc_index::bit_set::{BitSet, HybridBitSet, SparseBitMatrix};
+use rustc_index::{
+  bit_set::{BitSet, HybridBitSet, SparseBitMatrix},
+  vec::Idx,
+};
 use rustc_middle::mir::*;
 
 use super::utils::BodyExt;
+use crate::extensions::{is_extension_active, ExitMode};
 
+/// The reverse of a body's control-flow graph, starting from a virtual node that
+/// succeeds each of the body's exits.
 #[derive(Clone)]
 pub struct BodyReversed<'a, 'tcx> {
   body: &'a Body<'tcx>,
-  ret: BasicBlock,
+  exit: BasicBlock,
+  exits: BitSet<BasicBlock>,
   unreachable: BitSet<BasicBlock>,
 }
 
+/// Computes the immediate post-dominator of each block that can reach one of `exits`.
+///
+/// Blocks that are only post-dominated by the exits together are mapped to a
+/// virtual exit block, i.e. `BasicBlock::new(body.basic_blocks().len())`.
 pub fn compute_immediate_post_dominators(
   body: &Body,
-  ret: BasicBlock,
+  exits: &[BasicBlock],
 ) -> HashMap<BasicBlock, BasicBlock> {
   let nblocks = body.basic_blocks().len();
   let mut graph = BodyReversed {
     body,
-    ret,
-    unreachable: BitSet::new_empty(nblocks),
+    exit: BasicBlock::new(nblocks),
+    exits: BitSet::new_empty(nblocks),
+    unreachable: BitSet::new_empty(nblocks + 1),
   };
+  for exit in exits {
+    graph.exits.insert(*exit);
+  }
 
-  let reachable = iterate::post_order_from(&graph, ret);
+  let reachable = iterate::post_order_from(&graph, graph.exit);
   graph.unreachable.insert_all();
   for n in &reachable {
     graph.unreachable.remove(*n);
@@ -46,13 +61,13 @@ impl DirectedGraph for BodyReversed<'_, '_> {
 
 impl WithStartNode for BodyReversed<'_, '_> {
   fn start_node(&self) -> Self::Node {
-    self.ret
+    self.exit
   }
 }
 
 impl WithNumNodes for BodyReversed<'_, '_> {
   fn num_nodes(&self) -> usize {
-    self.body.basic_blocks().len()
+    self.body.basic_blocks().len() + 1
   }
 }
 
@@ -63,6 +78,10 @@ impl<'graph> GraphSuccessors<'graph> for BodyReversed<'_, '_> {
 
 impl WithSuccessors for BodyReversed<'_, '_> {
   fn successors(&self, node: Self::Node) -> <Self as GraphSuccessors<'_>>::Iter {
+    if node == self.exit {
+      return Box::new(self.exits.iter());
+    }
+
     Box::new(
       self.body.predecessors()[node]
         .iter()
@@ -79,11 +98,17 @@ impl<'graph> GraphPredecessors<'graph> for BodyReversed<'_, '_> {
 
 impl WithPredecessors for BodyReversed<'_, '_> {
   fn predecessors(&self, node: Self::Node) -> <Self as GraphPredecessors<'_>>::Iter {
+    if node == self.exit {
+      return Box::new(std::iter::empty());
+    }
+
+    let exit = self.exits.contains(node).then(|| self.exit);
     Box::new(
       self.body.basic_blocks()[node]
         .terminator()
         .successors()
-        .filter(|bb| !self.unreachable.contains(*bb)),
+        .filter(|bb| !self.unreachable.contains(*bb))
+        .chain(exit),
     )
   }
 }
@@ -118,11 +143,46 @@ impl ControlDependencies {
   /// Compute control dependencies for body.
   ///
   /// This computes union of the control dependencies for each return in the body.
+  /// If [`ExitMode::AllExits`] is active, then the exits where the body panics or
+  /// diverges are considered alongside each return, so a block that only executes
+  /// if an earlier call did not panic is control-dependent on that call.
   pub fn build(body: &Body) -> Self {
-    ControlDependencies(
+    let all_exits = is_extension_active(|mode| mode.exit_mode == ExitMode::AllExits);
+    let other_exits = if all_exits {
       body
-        .all_returns()
-        .map(|loc| ControlDependencies::build_for_return(body, loc.block))
+        .all_exits()
+        .map(|loc| loc.block)
+        .filter(|block| {
+          !matches!(
+            body.basic_blocks()[*block].terminator().kind,
+            TerminatorKind::Return
+          )
+        })
+        .collect::<Vec<_>>()
+    } else {
+      Vec::new()
+    };
+
+    // A body that never returns, e.g. one that always panics, still has its other
+    // exits, which are considered on their own
+    let returns = body
+      .all_returns()
+      .map(|loc| Some(loc.block))
+      .collect::<Vec<_>>();
+    let returns = if returns.is_empty() && !other_exits.is_empty() {
+      vec![None]
+    } else {
+      returns
+    };
+
+    ControlDependencies(
+      returns
+        .into_iter()
+        .map(|ret| {
+          let mut exits = other_exits.clone();
+          exits.extend(ret);
+          ControlDependencies::build_for_exits(body, &exits)
+        })
         .fold(
           SparseBitMatrix::new(body.basic_blocks().len()),
           |mut deps1, deps2| {
@@ -140,26 +200,31 @@ impl ControlDependencies {
   /// Compute control dependencies from post-dominator frontier.
   ///
   /// Frontier algorithm from "An Efficient Method of Computing Single Static Assignment Form", Cytron et al. 89
-  fn build_for_return(
+  fn build_for_exits(
     body: &Body,
-    ret: BasicBlock,
+    exits: &[BasicBlock],
   ) -> SparseBitMatrix<BasicBlock, BasicBlock> {
-    let idom = compute_immediate_post_dominators(body, ret);
+    let idom = compute_immediate_post_dominators(body, exits);
     log::debug!("idom={idom:?}");
 
+    let n = body.basic_blocks().len();
+    let exit = BasicBlock::new(n);
     let edges = body
       .basic_blocks()
       .indices()
       .filter_map(|bb| Some((*idom.get(&bb)?, bb)))
       .collect::<Vec<_>>();
-    let n = body.basic_blocks().len();
-    let dominator_tree = VecGraph::new(n, edges);
+    let dominator_tree = VecGraph::new(n + 1, edges);
 
-    let traversal = iterate::post_order_from(&dominator_tree, ret);
+    let traversal = iterate::post_order_from(&dominator_tree, exit);
 
     // Only use size = n b/c exit node shouldn't ever have a dominance frontier
     let mut df = SparseBitMatrix::new(n);
     for x in traversal {
+      if x == exit {
+        continue;
+      }
+
       let local = body.predecessors()[x].iter().copied();
       let up = dominator_tree
         .successors(x)
@@ -34,6 +34,19 @@ pub struct AnalysisResults<'tcx, A: Analysis<'tcx>> {
 }
 
 impl<'tcx, A: Analysis<'tcx>> AnalysisResults<'tcx, A> {
+  /// Creates analysis results from a previously computed state at each location.
+  crate fn from_raw(
+    analysis: A,
+    location_domain: Rc<LocationDomain>,
+    state: IndexVec<LocationIndex, A::Domain>,
+  ) -> Self {
+    AnalysisResults {
+      analysis,
+      location_domain,
+      state,
+    }
+  }
+
   pub fn visit_reachable_with<'mir, V>(&self, body: &'mir Body<'tcx>, visitor: &mut V)
   where
     V: ResultsVisitor<'mir, 'tcx, FlowState = A::Domain>,
@@ -0,0 +1,340 @@
+//! The global state (statics and thread-locals) that can be accessed by a body.
+//!
+//! A body accesses a static through a pointer held in a local, e.g.
+//! `_1 = const {X: *mut i32}` followed by `(*_1) = ...`, and a new local is created
+//! for each access. To relate the accesses, every place behind a pointer to a global
+//! is normalized to the same place behind the first pointer to that global
+//! (see [`Globals::normalize`]).
+//!
+//! A call may access any global that the callee (or one of its local callees) accesses,
+//! even if the caller never mentions that global. Such a global is represented in the
+//! caller by an opaque place whose local is not declared in the body. Opaque places
+//! have no projections and no aliases, and are only used to carry dependencies
+//! between calls.
+//!
+//! Only globals that can change are tracked: `static mut`s, statics with interior
+//! mutability, and thread-locals. Globals are only tracked with
+//! [`GlobalsMode::Track`](crate::extensions::GlobalsMode::Track).
+
+use std::cell::RefCell;
+
+use rustc_data_structures::fx::{FxHashMap as HashMap, FxHashSet as HashSet};
+use rustc_hir::def_id::{DefId, LocalDefId};
+use rustc_middle::{
+  mir::{visit::Visitor, *},
+  ty::{ConstKind, ParamEnv, Ty, TyCtxt, TyKind},
+};
+use rustc_span::DUMMY_SP;
+
+use super::{
+  borrowck_facts::get_body_with_borrowck_facts,
+  call_graph::{local_callee, CallGraph},
+};
+use crate::{
+  extensions::{is_extension_active, GlobalsMode},
+  mir::utils::PlaceExt,
+};
+
+/// The mutable globals accessed by a body, either directly or through its calls.
+pub struct Globals<'tcx> {
+  // The global pointed to by each local holding a pointer to a mutable global
+  pointers: HashMap<Local, DefId>,
+
+  // The place standing for each mutable global accessed by the body or its callees
+  places: HashMap<DefId, Place<'tcx>>,
+
+  // The mutable globals accessed by the local function called at each location
+  calls: HashMap<Location, Vec<DefId>>,
+
+  // The number of locals declared in the body, above which locals are opaque
+  num_locals: usize,
+}
+
+impl<'tcx> Globals<'tcx> {
+  pub fn build(tcx: TyCtxt<'tcx>, body: &Body<'tcx>) -> Self {
+    let num_locals = body.local_decls().len();
+    if !is_extension_active(|mode| mode.globals_mode == GlobalsMode::Track) {
+      return Globals {
+        pointers: HashMap::default(),
+        places: HashMap::default(),
+        calls: HashMap::default(),
+        num_locals,
+      };
+    }
+
+    let pointers = global_pointers(tcx, body);
+
+    let mut accessed_by_callee = HashMap::default();
+    let calls = body
+      .basic_blocks()
+      .iter_enumerated()
+      .filter_map(|(block, data)| {
+        let callee = local_callee(tcx, &data.terminator().kind)?;
+        let globals = accessed_by_callee
+          .entry(callee)
+          .or_insert_with(|| accessed_by_function(tcx, callee))
+          .clone();
+        Some((body.terminator_loc(block), globals))
+      })
+      .collect::<HashMap<_, _>>();
+
+    // Each global gets the place behind its first pointer in the body, if any
+    let mut pointer_locals = pointers.iter().collect::<Vec<_>>();
+    pointer_locals.sort_by_key(|(local, _)| **local);
+    let mut places = HashMap::default();
+    for (local, global) in pointer_locals {
+      places
+        .entry(*global)
+        .or_insert_with(|| tcx.mk_place_deref(Place::from_local(*local, tcx)));
+    }
+
+    // Otherwise the global gets an opaque place
+    let mut callee_globals = calls.values().flatten().copied().collect::<Vec<_>>();
+    callee_globals.sort_by_key(|global| tcx.def_path_hash(*global));
+    callee_globals.dedup();
+    for global in callee_globals {
+      if !places.contains_key(&global) {
+        let local = Local::from_usize(num_locals + places.len());
+        places.insert(global, Place::from_local(local, tcx));
+      }
+    }
+
+    Globals {
+      pointers,
+      places,
+      calls,
+      num_locals,
+    }
+  }
+
+  /// Replaces a place behind a pointer to a global with the place standing for
+  /// that global, e.g. `(*_5).0` with `(*_1).0` if `_1` and `_5` both point to `X`.
+  pub fn normalize(&self, place: Place<'tcx>, tcx: TyCtxt<'tcx>) -> Place<'tcx> {
+    match place.projection.first() {
+      Some(ProjectionElem::Deref) => match self.pointers.get(&place.local) {
+        Some(global) => {
+          let global_place = self.places[global];
+          let mut projection = global_place.projection.to_vec();
+          projection.extend(&place.projection[1 ..]);
+          Place::make(global_place.local, &projection, tcx)
+        }
+        None => place,
+      },
+      _ => place,
+    }
+  }
+
+  /// Returns true if `place` is (within) a global behind a pointer to the global.
+  pub fn is_global(&self, place: Place<'tcx>) -> bool {
+    matches!(place.projection.first(), Some(ProjectionElem::Deref))
+      && self.pointers.contains_key(&place.local)
+  }
+
+  /// Returns true if `place` stands for a global that is not accessed directly by
+  /// the body, and therefore has no type in the body.
+  pub fn is_opaque(&self, place: Place<'tcx>) -> bool {
+    place.local.as_usize() >= self.num_locals
+  }
+
+  /// Returns the places of the mutable globals that may be accessed by the call at
+  /// `location` with arguments `args`: the globals accessed by the callee if it is a
+  /// local function, and the globals pointed to by the arguments.
+  pub fn accessed_by_call(
+    &self,
+    tcx: TyCtxt<'tcx>,
+    args: &[Operand<'tcx>],
+    location: Location,
+  ) -> Vec<Place<'tcx>> {
+    let pointed_to = args.iter().filter_map(|arg| match arg {
+      Operand::Copy(place) | Operand::Move(place) => {
+        let local = place.as_local()?;
+        self.pointers.get(&local).copied()
+      }
+      Operand::Constant(constant) => global_of_constant(tcx, constant),
+    });
+
+    let globals = self
+      .calls
+      .get(&location)
+      .into_iter()
+      .flatten()
+      .copied()
+      .chain(pointed_to)
+      .collect::<HashSet<_>>();
+
+    let mut places = globals
+      .into_iter()
+      .filter_map(|global| self.places.get(&global).copied())
+      .collect::<Vec<_>>();
+    places.sort_by_key(|place| place.local);
+    places
+  }
+}
+
+/// Returns true if the value of `global` can change, i.e. if it is a thread-local,
+/// a `static mut`, or a static with interior mutability.
+pub fn is_mutable_global(tcx: TyCtxt<'_>, global: DefId) -> bool {
+  if tcx.is_thread_local_static(global) || tcx.is_mutable_static(global) {
+    return true;
+  }
+
+  let ty = tcx.type_of(global);
+  is_local_key(tcx, ty)
+    || (tcx.is_static(global) && !ty.is_freeze(tcx.at(DUMMY_SP), ParamEnv::empty()))
+}
+
+/// Returns true if `ty` is the type of a thread-local declared with `thread_local!`.
+fn is_local_key<'tcx>(tcx: TyCtxt<'tcx>, ty: Ty<'tcx>) -> bool {
+  match ty.kind() {
+    TyKind::Adt(adt_def, _) => tcx.def_path_str(adt_def.did()) == "std::thread::LocalKey",
+    _ => false,
+  }
+}
+
+/// Returns the global that `constant` points to, if any.
+///
+/// A `thread_local!` is a constant `LocalKey` (whose identity is its constant item),
+/// and is usually accessed through a promoted reference to the constant.
+pub fn global_of_constant<'tcx>(
+  tcx: TyCtxt<'tcx>,
+  constant: &Constant<'tcx>,
+) -> Option<DefId> {
+  if let Some(global) = constant.check_static_ptr(tcx) {
+    return Some(global);
+  }
+
+  let unevaluated = match constant.literal {
+    ConstantKind::Ty(c) => match c.val() {
+      ConstKind::Unevaluated(unevaluated) => unevaluated,
+      _ => return None,
+    },
+    ConstantKind::Val(..) => return None,
+  };
+
+  match unevaluated.promoted {
+    Some(promoted) => {
+      let body = &tcx.promoted_mir_opt_const_arg(unevaluated.def)[promoted];
+      body.basic_blocks().iter().find_map(|data| {
+        data
+          .statements
+          .iter()
+          .find_map(|statement| match &statement.kind {
+            StatementKind::Assign(box (_, Rvalue::Use(Operand::Constant(constant)))) => {
+              global_of_constant(tcx, constant)
+            }
+            _ => None,
+          })
+      })
+    }
+    None => is_local_key(tcx, constant.ty()).then(|| unevaluated.def.did),
+  }
+}
+
+/// Finds the locals of `body` holding a pointer to a mutable global, either from a
+/// constant or by copying or reborrowing another such pointer.
+fn global_pointers<'tcx>(tcx: TyCtxt<'tcx>, body: &Body<'tcx>) -> HashMap<Local, DefId> {
+  let mut finder = FindGlobalPointers {
+    tcx,
+    pointers: HashMap::default(),
+  };
+  loop {
+    let num_pointers = finder.pointers.len();
+    finder.visit_body(body);
+    if finder.pointers.len() == num_pointers {
+      break;
+    }
+  }
+  finder.pointers
+}
+
+struct FindGlobalPointers<'tcx> {
+  tcx: TyCtxt<'tcx>,
+  pointers: HashMap<Local, DefId>,
+}
+
+impl<'tcx> Visitor<'tcx> for FindGlobalPointers<'tcx> {
+  fn visit_assign(&mut self, place: &Place<'tcx>, rvalue: &Rvalue<'tcx>, _: Location) {
+    let local = match place.as_local() {
+      Some(local) => local,
+      None => return,
+    };
+
+    let global = match rvalue {
+      Rvalue::Use(Operand::Constant(constant)) => global_of_constant(self.tcx, constant),
+      Rvalue::ThreadLocalRef(global) => Some(*global),
+      Rvalue::Use(Operand::Copy(src) | Operand::Move(src)) => src
+        .as_local()
+        .and_then(|src| self.pointers.get(&src).copied()),
+      Rvalue::Ref(_, _, src) | Rvalue::AddressOf(_, src) => match src.as_ref() {
+        PlaceRef {
+          local: src,
+          projection: [ProjectionElem::Deref],
+        } => self.pointers.get(&src).copied(),
+        _ => None,
+      },
+      _ => None,
+    };
+
+    if let Some(global) = global {
+      if is_mutable_global(self.tcx, global) {
+        self.pointers.entry(local).or_insert(global);
+      }
+    }
+  }
+}
+
+thread_local! {
+  static ACCESSED_GLOBALS: RefCell<HashMap<LocalDefId, Vec<DefId>>> =
+    RefCell::new(HashMap::default());
+}
+
+/// Returns the mutable globals accessed by `def_id` or by any function it
+/// transitively calls in the local crate.
+///
+/// The globals are computed bottom-up over the call graph of `def_id` and memoized
+/// for each function in the call graph, so each body is only visited once per crate.
+pub fn accessed_by_function(tcx: TyCtxt<'_>, def_id: LocalDefId) -> Vec<DefId> {
+  if let Some(globals) = memoized_globals(def_id) {
+    return globals;
+  }
+
+  let call_graph = CallGraph::build(tcx, def_id);
+  for scc in call_graph.sccs() {
+    if memoized_globals(scc[0]).is_some() {
+      continue;
+    }
+
+    // The callees outside of the component come before it, so they are memoized
+    let mut globals = scc
+      .iter()
+      .flat_map(|function| {
+        let body = get_body_with_borrowck_facts(tcx, *function).simplified_body();
+        let callee_globals = body
+          .basic_blocks()
+          .iter()
+          .filter_map(|data| local_callee(tcx, &data.terminator().kind))
+          .filter_map(memoized_globals)
+          .flatten();
+        global_pointers(tcx, body)
+          .into_values()
+          .chain(callee_globals)
+          .collect::<Vec<_>>()
+      })
+      .collect::<Vec<_>>();
+    globals.sort_by_key(|global| tcx.def_path_hash(*global));
+    globals.dedup();
+
+    ACCESSED_GLOBALS.with(|accessed| {
+      let mut accessed = accessed.borrow_mut();
+      for function in scc {
+        accessed.insert(function, globals.clone());
+      }
+    });
+  }
+
+  memoized_globals(def_id).unwrap()
+}
+
+fn memoized_globals(def_id: LocalDefId) -> Option<Vec<DefId>> {
+  ACCESSED_GLOBALS.with(|accessed| accessed.borrow().get(&def_id).cloned())
+}
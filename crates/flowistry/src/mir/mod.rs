@@ -1,6 +1,9 @@
 //! Infrastructure for analyzing MIR that supports the information flow analysis.
 pub mod aliases;
 pub mod borrowck_facts;
+pub mod call_graph;
 pub mod control_dependencies;
 pub mod engine;
+pub mod globals;
+pub mod points_to;
 pub mod utils;
@@ -0,0 +1,416 @@
+//! A flow-insensitive points-to analysis for raw pointers and boxes.
+//!
+//! The region-based alias analysis in [`Aliases`](super::aliases::Aliases) cannot
+//! distinguish the targets of raw pointers and boxes, since their types have no
+//! lifetimes. This module computes an Andersen-style points-to relation for every
+//! raw pointer or box stored directly in a local (i.e. not behind a reference).
+//! The targets of a pointer are places whose address is taken, the loans of a
+//! reference cast to a raw pointer, or an abstract object for each call that
+//! produces a pointer (i.e. its allocation site).
+//!
+//! Pointers whose targets cannot be tracked, e.g. those passed in as arguments,
+//! loaded from behind another pointer, or borrowed themselves, may point to any
+//! target of any pointer.
+
+use rustc_data_structures::fx::{FxHashMap as HashMap, FxHashSet as HashSet};
+use rustc_hir::def_id::DefId;
+use rustc_middle::{
+  mir::{visit::Visitor, *},
+  ty::{RegionKind, TyCtxt, TyKind},
+};
+
+use super::{
+  aliases::{LoanMap, UNKNOWN_REGION},
+  utils::PlaceExt,
+};
+use crate::indexed::impls::PlaceSet;
+
+/// The targets of each raw pointer and box in a body.
+pub struct PointsTo<'tcx> {
+  targets: HashMap<Place<'tcx>, PlaceSet<'tcx>>,
+  unknown_targets: PlaceSet<'tcx>,
+}
+
+impl<'tcx> PointsTo<'tcx> {
+  /// Computes the points-to relation for `body`, given the loans computed by
+  /// the region-based alias analysis.
+  pub fn build(
+    tcx: TyCtxt<'tcx>,
+    def_id: DefId,
+    body: &Body<'tcx>,
+    loans: &LoanMap<'tcx>,
+  ) -> Self {
+    let mut nodes = HashSet::default();
+    let mut unknown = HashSet::default();
+    for local in body.local_decls().indices() {
+      let place = Place::from_local(local, tcx);
+      let pointers = place
+        .interior_pointers(tcx, body, def_id)
+        .remove(&UNKNOWN_REGION)
+        .unwrap_or_default();
+      for (pointer, _) in pointers {
+        if pointer.is_indirect() {
+          continue;
+        }
+        let pointer = pointer.normalize(tcx, def_id).collapse_indices(tcx);
+        nodes.insert(pointer);
+        if place.is_arg(body) {
+          unknown.insert(pointer);
+        }
+      }
+    }
+
+    let mut constraints = Constraints {
+      tcx,
+      def_id,
+      body,
+      loans,
+      nodes,
+      base: HashMap::default(),
+      edges: Vec::new(),
+      unknown,
+    };
+    constraints.visit_body(body);
+    constraints.solve()
+  }
+
+  /// Returns the places that `pointer` may point to.
+  ///
+  /// `pointer` must be a normalized place whose indices are collapsed
+  /// (see [`PlaceExt::collapse_indices`]).
+  pub fn targets(&self, pointer: Place<'tcx>) -> &PlaceSet<'tcx> {
+    self.targets.get(&pointer).unwrap_or(&self.unknown_targets)
+  }
+}
+
+enum Source<'tcx> {
+  Targets(Vec<Place<'tcx>>),
+  Pointer(Place<'tcx>),
+  Unknown,
+}
+
+struct Constraints<'a, 'tcx> {
+  tcx: TyCtxt<'tcx>,
+  def_id: DefId,
+  body: &'a Body<'tcx>,
+  loans: &'a LoanMap<'tcx>,
+  nodes: HashSet<Place<'tcx>>,
+
+  // base(p, t): t ∈ pts(p)
+  base: HashMap<Place<'tcx>, HashSet<Place<'tcx>>>,
+  // edge(p, q): pts(p) ⊆ pts(q)
+  edges: Vec<(Place<'tcx>, Place<'tcx>)>,
+  // The pointers whose targets cannot be tracked
+  unknown: HashSet<Place<'tcx>>,
+}
+
+impl<'tcx> Constraints<'_, 'tcx> {
+  /// Normalizes a pointer, where the pointers in all elements of an array or slice
+  /// are summarized by a single pointer.
+  fn normalize(&self, place: Place<'tcx>) -> Place<'tcx> {
+    place
+      .normalize(self.tcx, self.def_id)
+      .collapse_indices(self.tcx)
+  }
+
+  /// Returns the pointers stored within `place`, along with their projection
+  /// relative to `place`.
+  fn pointers_in(&self, place: Place<'tcx>) -> Vec<(Place<'tcx>, Vec<PlaceElem<'tcx>>)> {
+    if place.is_indirect() || !self.nodes.iter().any(|node| node.local == place.local) {
+      return Vec::new();
+    }
+    let place = self.normalize(place);
+    self
+      .nodes
+      .iter()
+      .filter(|node| {
+        node.local == place.local && node.projection.starts_with(place.projection)
+      })
+      .map(|node| (*node, node.projection[place.projection.len() ..].to_vec()))
+      .collect()
+  }
+
+  fn add(&mut self, pointer: Place<'tcx>, source: Source<'tcx>) {
+    match source {
+      Source::Targets(targets) => {
+        self.base.entry(pointer).or_default().extend(targets);
+      }
+      Source::Pointer(other) => {
+        self.edges.push((other, pointer));
+      }
+      Source::Unknown => {
+        self.unknown.insert(pointer);
+      }
+    }
+  }
+
+  /// The places that a reference `ptr` may point to, with `after` appended.
+  fn ref_targets(&self, ptr: Place<'tcx>, after: &[PlaceElem<'tcx>]) -> Source<'tcx> {
+    let ptr_ty = ptr.ty(self.body.local_decls(), self.tcx).ty;
+    let (region, orig_ty) = match ptr_ty.kind() {
+      TyKind::Ref(region, ty, _) => match region.kind() {
+        RegionKind::ReVar(region) => (region, *ty),
+        _ => return Source::Unknown,
+      },
+      _ => return Source::Unknown,
+    };
+    let targets = self
+      .loans
+      .get(&region)
+      .into_iter()
+      .flatten()
+      .map(|(loan, _)| {
+        let loan_ty = loan.ty(self.body.local_decls(), self.tcx).ty;
+        if orig_ty == loan_ty {
+          let mut projection = loan.projection.to_vec();
+          projection.extend(after.iter().copied());
+          Place::make(loan.local, &projection, self.tcx)
+        } else {
+          *loan
+        }
+      })
+      .collect();
+    Source::Targets(targets)
+  }
+
+  /// The places whose address is `&place`.
+  fn address_of(&self, place: Place<'tcx>) -> Source<'tcx> {
+    let (ptr, after) = match place.refs_in_projection().last() {
+      Some((ptr, after)) => (Place::from_ref(*ptr, self.tcx), *after),
+      None => return Source::Targets(vec![place]),
+    };
+    let ptr_ty = ptr.ty(self.body.local_decls(), self.tcx).ty;
+    if ptr_ty.is_ref() {
+      self.ref_targets(ptr, after)
+    } else if after.is_empty() && self.nodes.contains(&self.normalize(ptr)) {
+      Source::Pointer(self.normalize(ptr))
+    } else {
+      Source::Unknown
+    }
+  }
+
+  /// The source of the pointer at `projection` within the value of `operand`.
+  fn operand_source(
+    &self,
+    operand: &Operand<'tcx>,
+    projection: &[PlaceElem<'tcx>],
+  ) -> Source<'tcx> {
+    let place = match operand {
+      Operand::Copy(place) | Operand::Move(place) => *place,
+      Operand::Constant(..) => return Source::Targets(Vec::new()),
+    };
+
+    // A reference that is cast to a raw pointer
+    let ty = place.ty(self.body.local_decls(), self.tcx).ty;
+    if projection.is_empty() && ty.is_ref() {
+      return self.ref_targets(place, &[]);
+    }
+
+    let pointer = place.project_deeper(projection, self.tcx);
+    if pointer.is_indirect() {
+      return Source::Unknown;
+    }
+    let pointer = self.normalize(pointer);
+    if self.nodes.contains(&pointer) {
+      Source::Pointer(pointer)
+    } else {
+      Source::Unknown
+    }
+  }
+
+  fn rvalue_source(
+    &self,
+    rvalue: &Rvalue<'tcx>,
+    projection: &[PlaceElem<'tcx>],
+  ) -> Source<'tcx> {
+    match rvalue {
+      Rvalue::Use(operand)
+      | Rvalue::Cast(_, operand, _)
+      | Rvalue::ShallowInitBox(operand, _)
+      | Rvalue::BinaryOp(BinOp::Offset, box (operand, _)) => {
+        self.operand_source(operand, projection)
+      }
+      Rvalue::AddressOf(_, place) if projection.is_empty() => self.address_of(*place),
+      Rvalue::Aggregate(box kind, operands) => {
+        let projection = match (kind, projection) {
+          (
+            AggregateKind::Adt(_, variant, ..),
+            [ProjectionElem::Downcast(_, downcast), rest @ ..],
+          ) => {
+            if variant != downcast {
+              return Source::Targets(Vec::new());
+            }
+            rest
+          }
+          _ => projection,
+        };
+        match projection {
+          [ProjectionElem::Field(field, _), rest @ ..] => {
+            match operands.get(field.as_usize()) {
+              Some(operand) => self.operand_source(operand, rest),
+              None => Source::Unknown,
+            }
+          }
+          _ => Source::Unknown,
+        }
+      }
+      _ => Source::Unknown,
+    }
+  }
+
+  /// The sources of a pointer returned from a call: a fresh object allocated by
+  /// the call, or anything reachable from the arguments.
+  fn call_sources(
+    &self,
+    pointer: Place<'tcx>,
+    args: &[Operand<'tcx>],
+  ) -> Vec<Source<'tcx>> {
+    let mut targets = vec![self.tcx.mk_place_deref(pointer)];
+    let mut sources = Vec::new();
+    for arg in args.iter().filter_map(|arg| arg.place()) {
+      for (region, places) in arg.interior_pointers(self.tcx, self.body, self.def_id) {
+        if region == UNKNOWN_REGION {
+          sources.extend(places.into_iter().map(|(arg_pointer, _)| {
+            self.operand_source(&Operand::Copy(arg_pointer), &[])
+          }));
+        } else {
+          let loans = self.loans.get(&region).into_iter().flatten();
+          targets.extend(loans.map(|(loan, _)| *loan));
+        }
+      }
+    }
+    sources.push(Source::Targets(targets));
+    sources
+  }
+
+  fn solve(mut self) -> PointsTo<'tcx> {
+    let mut targets = self.base;
+    loop {
+      let mut changed = false;
+      for (from, to) in &self.edges {
+        if self.unknown.contains(from) {
+          changed |= self.unknown.insert(*to);
+        } else if let Some(from_targets) = targets.get(from).cloned() {
+          let to_targets = targets.entry(*to).or_default();
+          let orig_len = to_targets.len();
+          to_targets.extend(from_targets);
+          changed |= to_targets.len() != orig_len;
+        }
+      }
+      if !changed {
+        break;
+      }
+    }
+
+    // A pointer with unknown targets could point to anything that another pointer
+    // points to, or to anything behind another pointer
+    let unknown_targets = targets
+      .values()
+      .flatten()
+      .copied()
+      .chain(
+        self
+          .loans
+          .get(&UNKNOWN_REGION)
+          .into_iter()
+          .flatten()
+          .map(|(place, _)| *place),
+      )
+      .collect::<PlaceSet<'tcx>>();
+
+    self.nodes.retain(|node| !self.unknown.contains(node));
+    let targets = self
+      .nodes
+      .into_iter()
+      .map(|node| {
+        let node_targets = targets.remove(&node).unwrap_or_default();
+        (node, node_targets)
+      })
+      .collect();
+
+    PointsTo {
+      targets,
+      unknown_targets,
+    }
+  }
+}
+
+impl<'tcx> Visitor<'tcx> for Constraints<'_, 'tcx> {
+  fn visit_assign(
+    &mut self,
+    place: &Place<'tcx>,
+    rvalue: &Rvalue<'tcx>,
+    location: Location,
+  ) {
+    self.super_assign(place, rvalue, location);
+
+    for (pointer, projection) in self.pointers_in(*place) {
+      let source = self.rvalue_source(rvalue, &projection);
+      self.add(pointer, source);
+    }
+
+    // A pointer whose address is taken may be modified through that address
+    if let Rvalue::Ref(_, _, borrowed) | Rvalue::AddressOf(_, borrowed) = rvalue {
+      if !borrowed.is_indirect() {
+        let borrowed = self.normalize(*borrowed);
+        let overlapping = self
+          .nodes
+          .iter()
+          .filter(|node| {
+            node.local == borrowed.local
+              && (node.projection.starts_with(borrowed.projection)
+                || borrowed.projection.starts_with(node.projection))
+          })
+          .copied()
+          .collect::<Vec<_>>();
+        self.unknown.extend(overlapping);
+      }
+    }
+  }
+
+  fn visit_terminator(&mut self, terminator: &Terminator<'tcx>, location: Location) {
+    self.super_terminator(terminator, location);
+
+    match &terminator.kind {
+      TerminatorKind::Call {
+        args,
+        destination: Some((destination, _)),
+        ..
+      } => {
+        for (pointer, _) in self.pointers_in(*destination) {
+          for source in self.call_sources(pointer, args) {
+            self.add(pointer, source);
+          }
+        }
+      }
+      TerminatorKind::DropAndReplace { place, value, .. } => {
+        for (pointer, projection) in self.pointers_in(*place) {
+          let source = self.operand_source(value, &projection);
+          self.add(pointer, source);
+        }
+      }
+      TerminatorKind::Yield { resume_arg, .. } => {
+        for (pointer, _) in self.pointers_in(*resume_arg) {
+          self.add(pointer, Source::Unknown);
+        }
+      }
+      TerminatorKind::InlineAsm { operands, .. } => {
+        for operand in operands {
+          if let InlineAsmOperand::Out {
+            place: Some(place), ..
+          }
+          | InlineAsmOperand::InOut {
+            out_place: Some(place),
+            ..
+          } = operand
+          {
+            for (pointer, _) in self.pointers_in(*place) {
+              self.add(pointer, Source::Unknown);
+            }
+          }
+        }
+      }
+      _ => {}
+    }
+  }
+}
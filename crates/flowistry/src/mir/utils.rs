@@ -19,24 +19,28 @@ use rustc_infer::infer::TyCtxtInferExt;
 use rustc_middle::{
   mir::{
     pretty::write_mir_fn,
+    tcx::PlaceTy,
     visit::{PlaceContext, Visitor},
     MirPass, *,
   },
   traits::ObligationCause,
   ty::{
-    self, AdtKind, RegionKind, RegionVid, Ty, TyCtxt, TyKind, TypeAndMut, TypeVisitor,
+    self,
+    subst::{Subst, SubstsRef},
+    AdtKind, EarlyBinder, Instance, InstanceDef, ParamEnv, RegionKind, RegionVid, Ty,
+    TyCtxt, TyKind, TypeAndMut, TypeVisitor,
   },
 };
 use rustc_mir_dataflow::{fmt::DebugWithContext, graphviz, Analysis, Results};
 use rustc_span::{
-  source_map::SourceMap, BytePos, Pos, Span, SpanData, Symbol, SyntaxContext,
+  source_map::SourceMap, BytePos, Pos, Span, SpanData, Symbol, SyntaxContext, DUMMY_SP,
 };
 use rustc_target::abi::VariantIdx;
 use rustc_trait_selection::infer::InferCtxtExt;
 use smallvec::SmallVec;
 
 use crate::{
-  extensions::{is_extension_active, MutabilityMode},
+  extensions::{is_extension_active, InteriorMutabilityMode, MutabilityMode},
   mir::aliases::UNKNOWN_REGION,
 };
 
@@ -80,6 +84,96 @@ pub fn arg_mut_ptrs<'tcx>(
     .collect::<Vec<_>>()
 }
 
+/// Returns true if a value of type `ty` may be mutated through a shared reference,
+/// i.e. if `ty` contains an `UnsafeCell` (as in `Cell`, `RefCell`, `Mutex` or the
+/// atomics) and interior mutability is tracked.
+///
+/// Types that are not known to be `Freeze` within `def_id`, such as type parameters,
+/// are conservatively treated as interior mutable.
+pub fn is_interior_mutable<'tcx>(tcx: TyCtxt<'tcx>, def_id: DefId, ty: Ty<'tcx>) -> bool {
+  is_extension_active(|mode| {
+    mode.interior_mutability_mode == InteriorMutabilityMode::Track
+  }) && !tcx
+    .erase_regions(ty)
+    .is_freeze(tcx.at(DUMMY_SP), tcx.param_env(def_id))
+}
+
+/// Returns the function called by `func` from within `caller`, along with its substs.
+///
+/// Calls to trait methods are resolved to the method of the corresponding impl,
+/// if the impl can be determined from the caller's types. This includes calls
+/// through the Fn traits to a concrete closure.
+pub fn resolve_callee<'tcx>(
+  tcx: TyCtxt<'tcx>,
+  caller: DefId,
+  func: &Operand<'tcx>,
+) -> Option<(DefId, SubstsRef<'tcx>)> {
+  resolve_callee_with_substs(tcx, caller, func, None)
+}
+
+/// Like [`resolve_callee`], but first instantiates the generic parameters of `caller`
+/// with `caller_substs`, which must not contain any type parameters. For example,
+/// a call to `F: FnMut()` is resolved to the closure that `F` is instantiated with.
+pub fn resolve_callee_with_substs<'tcx>(
+  tcx: TyCtxt<'tcx>,
+  caller: DefId,
+  func: &Operand<'tcx>,
+  caller_substs: Option<SubstsRef<'tcx>>,
+) -> Option<(DefId, SubstsRef<'tcx>)> {
+  let (def_id, substs) = match func.constant()?.literal.ty().kind() {
+    TyKind::FnDef(def_id, substs) => (*def_id, *substs),
+    _ => return None,
+  };
+
+  let (substs, param_env) = match caller_substs {
+    Some(caller_substs) => (
+      EarlyBinder(substs).subst(tcx, caller_substs),
+      ParamEnv::reveal_all(),
+    ),
+    None => (substs, tcx.param_env(caller)),
+  };
+  Some(match Instance::resolve(tcx, param_env, def_id, substs) {
+    Ok(Some(Instance {
+      def: InstanceDef::Item(def),
+      substs,
+    })) => (def.did, substs),
+    // A closure called through FnOnce that implements FnMut or Fn is called
+    // through a shim, whose self type is the closure
+    Ok(Some(Instance {
+      def: InstanceDef::ClosureOnceShim { .. },
+      substs,
+    })) => match substs.type_at(0).kind() {
+      TyKind::Closure(def_id, substs) => (*def_id, *substs),
+      _ => (def_id, substs),
+    },
+    _ => (def_id, substs),
+  })
+}
+
+/// Returns the type of the field `field` of a place of type `ty`, including the
+/// upvars of closures. Returns `None` if the type does not have such a field, e.g.
+/// if it is a type parameter.
+pub fn field_ty<'tcx>(
+  tcx: TyCtxt<'tcx>,
+  ty: PlaceTy<'tcx>,
+  field: Field,
+) -> Option<Ty<'tcx>> {
+  let i = field.as_usize();
+  match ty.ty.kind() {
+    TyKind::Adt(adt_def, substs) => {
+      let variant = match ty.variant_index {
+        Some(variant_index) => adt_def.variants().get(variant_index)?,
+        None if !adt_def.is_enum() => adt_def.non_enum_variant(),
+        None => return None,
+      };
+      Some(variant.fields.get(i)?.ty(tcx, substs))
+    }
+    TyKind::Tuple(tys) => tys.get(i).copied(),
+    TyKind::Closure(_, substs) => substs.as_closure().upvar_tys().nth(i),
+    _ => None,
+  }
+}
+
 pub fn arg_places<'tcx>(args: &[Operand<'tcx>]) -> Vec<(usize, Place<'tcx>)> {
   args
     .iter()
@@ -137,9 +231,10 @@ impl PlaceRelation {
         match (elem1, elem2) {
           (Deref, Deref) => true,
           (Field(f1, _), Field(f2, _)) => f1 == f2,
-          (Index(_), Index(_)) => true,
-          (ConstantIndex { .. }, ConstantIndex { .. }) => true,
-          (Subslice { .. }, Subslice { .. }) => true,
+          (
+            Index(_) | ConstantIndex { .. } | Subslice { .. },
+            Index(_) | ConstantIndex { .. } | Subslice { .. },
+          ) => indices_may_overlap(elem1, elem2),
           (Downcast(_, v1), Downcast(_, v2)) => v1 == v2,
           _ => false,
         }
@@ -172,9 +267,60 @@ impl PlaceRelation {
   }
 }
 
+/// Returns true if two index projections may refer to the same element, where
+/// only distinct constant indices (or a constant index outside of a subslice) are
+/// known not to overlap.
+fn indices_may_overlap(elem1: PlaceElem<'_>, elem2: PlaceElem<'_>) -> bool {
+  use ProjectionElem::*;
+  match (elem1, elem2) {
+    (
+      ConstantIndex {
+        offset: offset1,
+        from_end: from_end1,
+        ..
+      },
+      ConstantIndex {
+        offset: offset2,
+        from_end: from_end2,
+        ..
+      },
+    ) => from_end1 != from_end2 || offset1 == offset2,
+    (
+      ConstantIndex {
+        offset,
+        from_end: index_from_end,
+        ..
+      },
+      Subslice {
+        from,
+        to,
+        from_end: slice_from_end,
+      },
+    )
+    | (
+      Subslice {
+        from,
+        to,
+        from_end: slice_from_end,
+      },
+      ConstantIndex {
+        offset,
+        from_end: index_from_end,
+        ..
+      },
+    ) => match (index_from_end, slice_from_end) {
+      (false, false) => from <= offset && offset < to,
+      (false, true) => from <= offset,
+      (true, true) => to < offset,
+      (true, false) => true,
+    },
+    _ => true,
+  }
+}
+
 pub struct PlaceCollector<'tcx> {
   pub tcx: TyCtxt<'tcx>,
-  pub places: Vec<(Place<'tcx>, Option<PlaceElem<'tcx>>)>,
+  pub places: Vec<(Place<'tcx>, Option<&'tcx [PlaceElem<'tcx>]>)>,
 }
 
 impl<'tcx> Visitor<'tcx> for PlaceCollector<'tcx> {
@@ -192,9 +338,14 @@ impl<'tcx> Visitor<'tcx> for PlaceCollector<'tcx> {
       Rvalue::Aggregate(box AggregateKind::Adt(def_id, idx, substs, _, _), ops) => {
         // In the case of _1 = aggregate { field1: op1, field2: op2, ... }
         // we want to remember which places correspond to which fields so the infoflow
-        // analysis can be field-sensitive for constructors.
-        let adt_def = self.tcx.adt_def(*def_id);
+        // analysis can be field-sensitive for constructors. Fields of an enum variant
+        // are projected through the variant, e.g. (_1 as Some).0
+        let tcx = self.tcx;
+        let adt_def = tcx.adt_def(*def_id);
         let variant = adt_def.variant(*idx);
+        let downcast = adt_def
+          .is_enum()
+          .then(|| ProjectionElem::Downcast(Some(variant.name), *idx));
         let places = variant
           .fields
           .iter()
@@ -203,8 +354,9 @@ impl<'tcx> Visitor<'tcx> for PlaceCollector<'tcx> {
           .filter_map(|((i, field), op)| {
             let place = op.to_place()?;
             let field =
-              ProjectionElem::Field(Field::from_usize(i), field.ty(self.tcx, substs));
-            Some((place, Some(field)))
+              ProjectionElem::Field(Field::from_usize(i), field.ty(tcx, substs));
+            let projection = downcast.into_iter().chain([field]).collect::<Vec<_>>();
+            Some((place, Some(&**tcx.intern_place_elems(&projection))))
           });
         self.places.extend(places);
       }
@@ -324,6 +476,7 @@ pub trait PlaceExt<'tcx> {
   ) -> Vec<Place<'tcx>>;
   fn to_string(&self, tcx: TyCtxt<'tcx>, body: &Body<'tcx>) -> Option<String>;
   fn normalize(&self, tcx: TyCtxt<'tcx>, def_id: DefId) -> Place<'tcx>;
+  fn collapse_indices(&self, tcx: TyCtxt<'tcx>) -> Place<'tcx>;
 }
 
 impl<'tcx> PlaceExt<'tcx> for Place<'tcx> {
@@ -475,7 +628,22 @@ impl<'tcx> PlaceExt<'tcx> for Place<'tcx> {
           sym.map(|s| s.to_string()).unwrap_or_else(|| "??".into())
         ),
         ProjectionElem::Index(_) => format!("{s}[]"),
-        _ => unimplemented!(),
+        ProjectionElem::ConstantIndex {
+          offset, from_end, ..
+        } => {
+          if from_end {
+            format!("{s}[-{offset}]")
+          } else {
+            format!("{s}[{offset}]")
+          }
+        }
+        ProjectionElem::Subslice { from, to, from_end } => {
+          if from_end {
+            format!("{s}[{from}..-{to}]")
+          } else {
+            format!("{s}[{from}..{to}]")
+          }
+        }
       };
 
     Some(
@@ -506,8 +674,31 @@ impl<'tcx> PlaceExt<'tcx> for Place<'tcx> {
     let projection = place
       .projection
       .into_iter()
-      .filter_map(|elem| match elem {
+      .map(|elem| match elem {
         // Map all indexes [i] to [0] since they should be considered equal
+        ProjectionElem::Index(_) => ProjectionElem::Index(Local::from_usize(0)),
+        // Constant indexes are kept distinct, but the same element can be
+        // accessed with different minimum lengths, e.g. by [x, ..] and [x, _, ..]
+        ProjectionElem::ConstantIndex {
+          offset, from_end, ..
+        } => ProjectionElem::ConstantIndex {
+          offset,
+          min_length: if from_end { offset } else { offset + 1 },
+          from_end,
+        },
+        _ => elem,
+      })
+      .collect::<Vec<_>>();
+
+    Place::make(place.local, &projection, tcx)
+  }
+
+  fn collapse_indices(&self, tcx: TyCtxt<'tcx>) -> Place<'tcx> {
+    let projection = self
+      .projection
+      .into_iter()
+      .filter_map(|elem| match elem {
+        // Map constant indexes to the index of an arbitrary element
         ProjectionElem::Index(_) | ProjectionElem::ConstantIndex { .. } => {
           Some(ProjectionElem::Index(Local::from_usize(0)))
         }
@@ -518,7 +709,7 @@ impl<'tcx> PlaceExt<'tcx> for Place<'tcx> {
       })
       .collect::<Vec<_>>();
 
-    Place::make(place.local, &projection, tcx)
+    Place::make(self.local, &projection, tcx)
   }
 }
 
@@ -632,10 +823,14 @@ impl<'tcx> TypeVisitor<'tcx> for CollectRegions<'tcx> {
         StoppingCondition::BeforeRefs => {}
       },
 
-      TyKind::Closure(_, substs) | TyKind::Generator(_, substs, _) => {
+      TyKind::Closure(_, substs) => {
         self.visit_ty(substs.as_closure().tupled_upvars_ty());
       }
 
+      TyKind::Generator(_, substs, _) => {
+        self.visit_ty(substs.as_generator().tupled_upvars_ty());
+      }
+
       TyKind::RawPtr(TypeAndMut { ty, .. }) => {
         self.visit_region(tcx.mk_region(RegionKind::ReVar(UNKNOWN_REGION)));
         self.place_stack.push(ProjectionElem::Deref);
@@ -711,11 +906,12 @@ impl<'tcx> TypeVisitor<'tcx> for CollectRegions<'tcx> {
       _ => unreachable!("{:?}: {:?}", self.ty_stack.first().unwrap(), region),
     };
 
-    let mutability = if self
-      .ty_stack
-      .iter()
-      .any(|ty| ty.is_ref() && ty.ref_mutability().unwrap() == Mutability::Not)
-    {
+    let mutability = if self.ty_stack.iter().any(|ty| match ty.kind() {
+      TyKind::Ref(_, pointee, Mutability::Not) => {
+        !is_interior_mutable(self.tcx, self.def_id, *pointee)
+      }
+      _ => false,
+    }) {
       Mutability::Not
     } else {
       Mutability::Mut
@@ -744,6 +940,14 @@ pub trait BodyExt<'tcx> {
     Self: 'a;
   fn all_returns(&self) -> Self::AllReturnsIter<'_>;
 
+  type AllExitsIter<'a>: Iterator<Item = Location>
+  where
+    Self: 'a;
+  /// Returns the locations where execution of the body ends: returns, but also
+  /// unwinding, aborts, and calls that diverge without unwinding.
+  /// Unreachable terminators are not exits, since they can never be executed.
+  fn all_exits(&self) -> Self::AllExitsIter<'_>;
+
   type AllLocationsIter<'a>: Iterator<Item = Location>
   where
     Self: 'a;
@@ -772,6 +976,22 @@ impl<'tcx> BodyExt<'tcx> for Body<'tcx> {
       })
   }
 
+  type AllExitsIter<'a> = impl Iterator<Item = Location>   where Self: 'a;
+  fn all_exits(&self) -> Self::AllExitsIter<'_> {
+    self
+      .basic_blocks()
+      .iter_enumerated()
+      .filter_map(|(block, data)| {
+        let terminator = data.terminator();
+        let is_exit = terminator.successors().next().is_none()
+          && !matches!(terminator.kind, TerminatorKind::Unreachable);
+        is_exit.then(|| Location {
+          block,
+          statement_index: data.statements.len(),
+        })
+      })
+  }
+
   type AllLocationsIter<'a> = impl Iterator<Item = Location>   where Self: 'a;
   fn all_locations(&self) -> Self::AllLocationsIter<'_> {
     self
@@ -3,7 +3,7 @@ use log::trace;
 use rustc_hir::{
   self as hir,
   intravisit::{self, Visitor as HirVisitor},
-  Expr, ExprKind, MatchSource, Node, Param, Stmt,
+  Expr, ExprKind, LocalSource, MatchSource, Node, Param, Stmt, StmtKind,
 };
 use rustc_span::{BytePos, Span, SpanData};
 
@@ -152,6 +152,17 @@ impl<'hir> HirVisitor<'hir> for HirSpanCollector<'_, 'hir, '_> {
   }
 
   fn visit_stmt(&mut self, stmt: &'hir Stmt<'hir>) {
+    // The body of an async fn starts by moving each parameter into a local with
+    // `let x = x;`. That statement has the span of the parameter's pattern, so it is
+    // skipped in favor of the parameter itself (see Spanner::new).
+    if let StmtKind::Local(hir::Local {
+      source: LocalSource::AsyncFn,
+      ..
+    }) = stmt.kind
+    {
+      return;
+    }
+
     intravisit::walk_stmt(self, stmt);
 
     let span = try_span!(self, stmt.span);
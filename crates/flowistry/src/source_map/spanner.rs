@@ -5,13 +5,14 @@ use std::rc::Rc;
 use either::Either;
 use log::trace;
 use rustc_hir::{
-  self as hir, intravisit::Visitor as HirVisitor, BodyId, ExprKind, MatchSource, Node,
+  self as hir, intravisit::Visitor as HirVisitor, AsyncGeneratorKind, BodyId, ExprKind,
+  GeneratorKind, MatchSource, Node,
 };
 use rustc_middle::{
   mir::{
     self, visit::Visitor as MirVisitor, Body, StatementKind, TerminatorKind, RETURN_PLACE,
   },
-  ty::TyCtxt,
+  ty::{DefIdTree, TyCtxt},
 };
 use rustc_span::{source_map::Spanned, Span, SpanData};
 
@@ -43,7 +44,22 @@ where
   pub fn new(tcx: TyCtxt<'tcx>, body_id: BodyId, body: &Body<'tcx>) -> Self {
     let hir = tcx.hir();
     let hir_body = hir.body(body_id);
-    let owner = hir.body_owner(body_id);
+
+    // The body of an `async fn` is a generator nested in the function, whose
+    // parameters and return type are only found on the function itself
+    let generator_kind = tcx.generator_kind(hir.body_owner_def_id(body_id));
+    let async_fn_body = match generator_kind {
+      Some(GeneratorKind::Async(AsyncGeneratorKind::Fn)) => {
+        let fn_def_id = tcx.local_parent(hir.body_owner_def_id(body_id));
+        Some(hir.body(hir.body_owned_by(hir.local_def_id_to_hir_id(fn_def_id))))
+      }
+      _ => None,
+    };
+    let owner = match async_fn_body {
+      Some(fn_body) => hir.body_owner(fn_body.id()),
+      None => hir.body_owner(body_id),
+    };
+
     let item_span = hir.span_with_body(owner);
     let ret_span = hir.fn_decl_by_hir_id(owner).unwrap().output.span();
 
@@ -65,6 +81,11 @@ where
 
     let mut hir_collector = HirSpanCollector(&mut spanner);
     hir_collector.visit_body(hir_body);
+    if let Some(fn_body) = async_fn_body {
+      for param in fn_body.params {
+        hir_collector.visit_param(param);
+      }
+    }
 
     let mut mir_collector = MirSpanCollector(&mut spanner, body);
     mir_collector.visit_body(body);
@@ -266,6 +287,25 @@ where
     vec.dedup();
     vec
   }
+
+  /// Returns the places and locations in the MIR that correspond to `span`, grouped
+  /// by the place they refer to. Use `concat` to slice on all of them together.
+  pub fn span_to_targets(
+    &self,
+    span: Span,
+  ) -> Vec<Vec<(mir::Place<'tcx>, mir::Location)>> {
+    self
+      .span_to_places(span)
+      .into_iter()
+      .map(|mir_span| {
+        mir_span
+          .locations
+          .iter()
+          .map(|location| (mir_span.place, *location))
+          .collect::<Vec<_>>()
+      })
+      .collect()
+  }
 }
 
 #[cfg(test)]
@@ -321,7 +361,7 @@ mod test {
         &["w.0"],
         &["w.0"],
       ];
-      for (input_span, desired) in spans.into_iter().zip(expected.into_iter()) {
+      for (input_span, desired) in spans.into_iter().zip(expected.iter()) {
         let outputs = spanner.span_to_places(input_span);
         let snippets = outputs
           .into_iter()
@@ -361,7 +401,7 @@ mod test {
           &spanner.hir_span_tree,
         )
         .collect::<Vec<_>>();
-        let desired_set = desired.into_iter().copied().collect::<HashSet<_>>();
+        let desired_set = desired.iter().copied().collect::<HashSet<_>>();
         let output_snippets = enclosing
           .remove(0)
           .get_spans(span_type)
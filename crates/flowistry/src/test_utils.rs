@@ -5,7 +5,6 @@ use std::{fs, io, panic, path::Path, process::Command};
 use anyhow::{anyhow, bail, Context, Result};
 use fluid_let::fluid_set;
 use log::{debug, info};
-use rustc_borrowck::BodyWithBorrowckFacts;
 use rustc_data_structures::fx::{FxHashMap as HashMap, FxHashSet as HashSet};
 use rustc_hir::{BodyId, ItemKind};
 use rustc_middle::ty::TyCtxt;
@@ -13,8 +12,11 @@ use rustc_span::{source_map::FileLoader, BytePos, Span, SyntaxContext};
 use unicode_segmentation::UnicodeSegmentation;
 
 use crate::{
-  extensions::{ContextMode, EvalMode, MutabilityMode, PointerMode, EVAL_MODE},
-  infoflow::{self},
+  extensions::{
+    ContextMode, ControlMode, EvalMode, ExitMode, GlobalsMode, InteriorMutabilityMode,
+    MutabilityMode, PointerMode, SliceMode, EVAL_MODE,
+  },
+  infoflow::{self, models},
   mir::{borrowck_facts::{self, CachedSimplifedBodyWithFacts}, utils::BodyExt},
   source_map::{find_enclosing_bodies, GraphemeIndices, Range, Spanner, ToSpan},
 };
@@ -45,9 +47,10 @@ lazy_static::lazy_static! {
 pub fn compile_body_with_range(
   input: impl Into<String>,
   target: impl ToSpan,
+  extra_args: &[&str],
   callback: impl for<'tcx> FnOnce(TyCtxt<'tcx>, BodyId, &CachedSimplifedBodyWithFacts<'tcx>) + Send,
 ) {
-  compile(input, |tcx| {
+  compile_with_args(input, extra_args, |tcx| {
     let body_id = find_enclosing_bodies(tcx, target.to_span(tcx).unwrap())
       .next()
       .unwrap();
@@ -83,6 +86,17 @@ pub fn compile_body(
 }
 
 pub fn compile(input: impl Into<String>, callback: impl FnOnce(TyCtxt<'_>) + Send) {
+  compile_with_args(input, &[], callback)
+}
+
+/// Same as [`compile`], but with the additional rustc arguments `extra_args`, e.g.
+/// [`ANNOTATION_RUSTC_ARGS`](models::ANNOTATION_RUSTC_ARGS) for inputs with flow
+/// annotations.
+pub fn compile_with_args(
+  input: impl Into<String>,
+  extra_args: &[&str],
+  callback: impl FnOnce(TyCtxt<'_>) + Send,
+) {
   let mut callbacks = TestCallbacks {
     callback: Some(callback),
   };
@@ -90,7 +104,11 @@ pub fn compile(input: impl Into<String>, callback: impl FnOnce(TyCtxt<'_>) + Sen
     "rustc dummy.rs --crate-type lib --edition=2021 -Z identify-regions -Z mir-opt-level=0 --allow warnings --sysroot {}",
     &*SYSROOT
   );
-  let args = args.split(' ').map(|s| s.to_string()).collect::<Vec<_>>();
+  let args = args
+    .split(' ')
+    .chain(extra_args.iter().copied())
+    .map(|s| s.to_string())
+    .collect::<Vec<_>>();
 
   rustc_driver::catch_fatal_errors(|| {
     let mut compiler = rustc_driver::RunCompiler::new(&args, &mut callbacks);
@@ -101,6 +119,84 @@ pub fn compile(input: impl Into<String>, callback: impl FnOnce(TyCtxt<'_>) + Sen
   .unwrap();
 }
 
+/// Compiles `input` as a library named `crate_name` with the additional rustc arguments
+/// `extra_args`, calling `callback` after the crate is analyzed. Unlike [`compile`],
+/// compilation continues afterward so that the crate's metadata is written to `out_dir`,
+/// where it is found by crates that import it.
+pub fn compile_crate(
+  input: impl Into<String>,
+  crate_name: &str,
+  out_dir: &Path,
+  extra_args: &[&str],
+  callback: impl FnOnce(TyCtxt<'_>) + Send,
+) {
+  let mut callbacks = CrateCallbacks {
+    callback: Some(callback),
+  };
+  let out_dir = out_dir.to_str().unwrap();
+  let args = [
+    "rustc",
+    "dummy.rs",
+    "--crate-type",
+    "lib",
+    "--crate-name",
+    crate_name,
+    "--emit",
+    "metadata",
+    "--out-dir",
+    out_dir,
+    "-L",
+    out_dir,
+    "--edition=2021",
+    "-Z",
+    "identify-regions",
+    "-Z",
+    "mir-opt-level=0",
+    "--allow",
+    "warnings",
+    "--sysroot",
+    &*SYSROOT,
+  ];
+  let args = args
+    .into_iter()
+    .chain(extra_args.iter().copied())
+    .map(|s| s.to_string())
+    .collect::<Vec<_>>();
+
+  rustc_driver::catch_fatal_errors(|| {
+    let mut compiler = rustc_driver::RunCompiler::new(&args, &mut callbacks);
+    compiler.set_file_loader(Some(Box::new(StringLoader(input.into()))));
+    compiler.run()
+  })
+  .unwrap()
+  .unwrap();
+}
+
+struct CrateCallbacks<Cb> {
+  callback: Option<Cb>,
+}
+
+impl<Cb> rustc_driver::Callbacks for CrateCallbacks<Cb>
+where
+  Cb: FnOnce(TyCtxt<'_>),
+{
+  fn config(&mut self, config: &mut rustc_interface::Config) {
+    config.override_queries = Some(borrowck_facts::override_queries);
+  }
+
+  fn after_analysis<'tcx>(
+    &mut self,
+    _compiler: &rustc_interface::interface::Compiler,
+    queries: &'tcx rustc_interface::Queries<'tcx>,
+  ) -> rustc_driver::Compilation {
+    queries.global_ctxt().unwrap().peek_mut().enter(|tcx| {
+      let callback = self.callback.take().unwrap();
+      callback(tcx);
+    });
+    rustc_driver::Compilation::Continue
+  }
+}
+
 struct TestCallbacks<Cb> {
   callback: Option<Cb>,
 }
@@ -293,7 +389,53 @@ pub fn test_command_output(
   output_fn: impl for<'a, 'hir, 'tcx> Fn(
       infoflow::FlowResults<'a, 'tcx, infoflow::TransitiveFlowDomain<'tcx>>,
       Spanner<'hir, 'tcx>,
-      Span,
+      Vec<Span>,
+    ) -> Vec<Span>
+    + Send
+    + Sync,
+) {
+  test_output(
+    path,
+    expected,
+    |tcx, body_id, body_with_facts, spanner, targets| {
+      let results = infoflow::compute_flow(tcx, body_id, body_with_facts);
+      output_fn(results, spanner, targets)
+    },
+  );
+}
+
+/// Same as [`test_command_output`], but with the results of
+/// [`compute_flow_with_kinds`](infoflow::compute_flow_with_kinds).
+pub fn test_kinds_output(
+  path: &Path,
+  expected: Option<&Path>,
+  output_fn: impl for<'a, 'hir, 'tcx> Fn(
+      infoflow::FlowResults<'a, 'tcx, infoflow::KindedFlowDomain<'tcx>>,
+      Spanner<'hir, 'tcx>,
+      Vec<Span>,
+    ) -> Vec<Span>
+    + Send
+    + Sync,
+) {
+  test_output(
+    path,
+    expected,
+    |tcx, body_id, body_with_facts, spanner, targets| {
+      let results = infoflow::compute_flow_with_kinds(tcx, body_id, body_with_facts);
+      output_fn(results, spanner, targets)
+    },
+  );
+}
+
+fn test_output(
+  path: &Path,
+  expected: Option<&Path>,
+  output_fn: impl for<'hir, 'tcx> Fn(
+      TyCtxt<'tcx>,
+      BodyId,
+      &CachedSimplifedBodyWithFacts<'tcx>,
+      Spanner<'hir, 'tcx>,
+      Vec<Span>,
     ) -> Vec<Span>
     + Send
     + Sync,
@@ -303,11 +445,19 @@ pub fn test_command_output(
     let input = String::from_utf8(fs::read(path)?)?;
 
     let (input_clean, input_ranges) = parse_range_map(&input, vec![("`(", ")`")])?;
-    let target = input_ranges["`("][0].clone();
+    let targets = input_ranges["`("].clone();
+
+    let header = input.lines().next().unwrap();
+    let extra_args = if header.starts_with("/*") && header.contains("annotations") {
+      models::ANNOTATION_RUSTC_ARGS
+    } else {
+      &[]
+    };
 
     compile_body_with_range(
       input_clean.clone(),
-      target.clone(),
+      targets[0].clone(),
+      extra_args,
       move |tcx, body_id, body_with_facts| {
         let header = input.lines().next().unwrap();
         let mut mode = EvalMode::default();
@@ -315,21 +465,53 @@ pub fn test_command_output(
           if header.contains("recurse") {
             mode.context_mode = ContextMode::Recurse;
           }
+          if header.contains("summary") {
+            mode.context_mode = ContextMode::Summary;
+          }
           if header.contains("ignoremut") {
             mode.mutability_mode = MutabilityMode::IgnoreMut;
           }
           if header.contains("conservative") {
             mode.pointer_mode = PointerMode::Conservative;
           }
+          if header.contains("flowsensitive") {
+            mode.pointer_mode = PointerMode::FlowSensitive;
+          }
+          if header.contains("pointsto") {
+            mode.pointer_mode = PointerMode::PointsTo;
+          }
+          if let Some(depth_limit) = header
+            .split_whitespace()
+            .find_map(|word| word.strip_prefix("depth="))
+          {
+            mode.depth_limit = depth_limit.parse().unwrap();
+          }
+          if header.contains("dataonly") {
+            mode.control_mode = ControlMode::DataOnly;
+          }
+          if header.contains("allexits") {
+            mode.exit_mode = ExitMode::AllExits;
+          }
+          if header.contains("interiormut") {
+            mode.interior_mutability_mode = InteriorMutabilityMode::Track;
+          }
+          if header.contains("globals") {
+            mode.globals_mode = GlobalsMode::Track;
+          }
+          if header.contains("thin") {
+            mode.slice_mode = SliceMode::Thin;
+          }
         }
 
         fluid_set!(EVAL_MODE, &mode);
 
-        let target = target.to_span(tcx).unwrap();
-        let results = infoflow::compute_flow(tcx, body_id, body_with_facts);
+        let targets = targets
+          .iter()
+          .map(|target| target.to_span(tcx).unwrap())
+          .collect::<Vec<_>>();
         let spanner = Spanner::new(tcx, body_id, body_with_facts.simplified_body());
 
-        let actual = output_fn(results, spanner, target)
+        let actual = output_fn(tcx, body_id, body_with_facts, spanner, targets)
           .into_iter()
           .map(|span| Range::from_span(span, tcx.sess.source_map()))
           .collect::<Result<HashSet<_>>>()
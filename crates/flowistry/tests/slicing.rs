@@ -3,7 +3,7 @@
 extern crate rustc_span;
 
 use flowistry::{
-  infoflow::{self, Direction},
+  infoflow::{self, DependencyKind, Direction},
   mir::utils::SpanExt,
   test_utils,
 };
@@ -12,18 +12,8 @@ use test_log::test;
 
 fn slice(dir: &str, direction: Direction) {
   test_utils::run_tests(dir, |path, expected| {
-    test_utils::test_command_output(path, expected, |results, spanner, target| {
-      let places = spanner.span_to_places(target);
-      let targets = places
-        .iter()
-        .map(|mir_span| {
-          mir_span
-            .locations
-            .iter()
-            .map(|location| (mir_span.place, *location))
-            .collect::<Vec<_>>()
-        })
-        .collect();
+    test_utils::test_command_output(path, expected, |results, spanner, targets| {
+      let targets = spanner.span_to_targets(targets[0]);
       log::debug!("targets={targets:#?}");
 
       let deps =
@@ -34,6 +24,42 @@ fn slice(dir: &str, direction: Direction) {
   });
 }
 
+// The first target of a chop test is the source, and the second is the sink
+fn chop(dir: &str) {
+  test_utils::run_tests(dir, |path, expected| {
+    test_utils::test_command_output(path, expected, |results, spanner, targets| {
+      let sources = spanner.span_to_targets(targets[0]).concat();
+      let sinks = spanner.span_to_targets(targets[1]).concat();
+      log::debug!("sources={sources:#?}, sinks={sinks:#?}");
+
+      infoflow::compute_chop_spans(&results, sources, sinks, &spanner)
+    });
+  });
+}
+
+// The kind of the dependencies in a kinds test is the prefix of the test's name
+fn kinds(dir: &str) {
+  test_utils::run_tests(dir, |path, expected| {
+    let name = path.file_name().unwrap().to_string_lossy();
+    let kind = match name.split('_').next().unwrap() {
+      "data" => DependencyKind::Data,
+      "control" => DependencyKind::Control,
+      "alias" => DependencyKind::Alias,
+      "call" => DependencyKind::Call,
+      prefix => panic!("Unknown dependency kind: {prefix}"),
+    };
+    test_utils::test_kinds_output(path, expected, |results, spanner, targets| {
+      let targets = spanner.span_to_targets(targets[0]);
+      log::debug!("targets={targets:#?}");
+
+      let deps =
+        infoflow::compute_dependency_kind_spans(&results, targets, kind, &spanner);
+
+      Span::merge_overlaps(deps.into_iter().flatten().collect())
+    });
+  });
+}
+
 #[test]
 fn test_backward_slice() {
   slice("backward_slice", Direction::Backward);
@@ -48,3 +74,13 @@ fn test_forward_slice() {
 fn test_extensions() {
   slice("extensions", Direction::Backward);
 }
+
+#[test]
+fn test_chop() {
+  chop("chop");
+}
+
+#[test]
+fn test_kinds() {
+  kinds("kinds");
+}
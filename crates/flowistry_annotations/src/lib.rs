@@ -0,0 +1,45 @@
+//! Annotations that describe the information flow of a function to Flowistry.
+//!
+//! The annotations are meant to be used under the name `flowistry`, e.g. by adding
+//! `use flowistry_annotations as flowistry;` to a module. The crate must also register
+//! the `flowistry_tool` tool that the annotations expand to when compiled by Flowistry,
+//! by adding the following attribute to the crate root:
+//!
+//! ```ignore
+//! #![cfg_attr(flowistry, feature(register_tool), register_tool(flowistry_tool))]
+//! ```
+//!
+//! For example:
+//!
+//! ```ignore
+//! use flowistry_annotations as flowistry;
+//!
+//! #[flowistry::flows(ret <- a, *b <- (b, c))]
+//! fn f(a: i32, b: &mut i32, c: i32) -> i32 {
+//!   *b += c;
+//!   a
+//! }
+//! ```
+//!
+//! Annotations have no effect outside of Flowistry.
+
+use proc_macro::TokenStream;
+
+/// Overrides Flowistry's analysis of calls to the annotated function.
+///
+/// The attribute takes a comma-separated list of flows `place <- inputs`. The `place`
+/// is either `ret` for the return value, or an argument name prefixed by one `*` per
+/// dereference for a place that the function mutates. The `inputs` are either a single
+/// argument name, or a parenthesized, comma-separated list of argument names.
+/// Any mutable place reachable from an argument `a` can be written as `reach a`.
+///
+/// Places that are not listed are assumed to not be mutated by the function.
+#[proc_macro_attribute]
+pub fn flows(attr: TokenStream, item: TokenStream) -> TokenStream {
+  let mut output: TokenStream =
+    format!("#[cfg_attr(flowistry, flowistry_tool::flows({attr}))]")
+      .parse()
+      .unwrap();
+  output.extend(item);
+  output
+}
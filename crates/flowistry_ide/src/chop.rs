@@ -0,0 +1,44 @@
+use anyhow::Result;
+use flowistry::{
+  infoflow,
+  mir::{borrowck_facts::get_body_with_borrowck_facts, utils::SpanExt},
+  source_map::{self, Range, ToSpan},
+};
+use rustc_hir::BodyId;
+use rustc_middle::ty::TyCtxt;
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+pub struct ChopOutput {
+  pub ranges: Vec<Range>,
+}
+
+/// Computes the code on any path of dependencies from the place at `source` to the
+/// place at `sink`.
+pub fn chop(
+  tcx: TyCtxt,
+  body_id: BodyId,
+  source: &Range,
+  sink: &Range,
+) -> Result<ChopOutput> {
+  let def_id = tcx.hir().body_owner_def_id(body_id);
+  let body_with_facts = get_body_with_borrowck_facts(tcx, def_id);
+  let body = body_with_facts.simplified_body();
+  let results = &infoflow::compute_flow(tcx, body_id, body_with_facts);
+
+  let source_map = tcx.sess.source_map();
+  let spanner = source_map::Spanner::new(tcx, body_id, body);
+
+  let sources = spanner.span_to_targets(source.to_span(tcx)?).concat();
+  let sinks = spanner.span_to_targets(sink.to_span(tcx)?).concat();
+  let spans = infoflow::compute_chop_spans(results, sources, sinks, &spanner);
+
+  let ranges = spans
+    .into_iter()
+    .filter_map(|span| span.trim_leading_whitespace(source_map))
+    .flatten()
+    .filter_map(|span| Range::from_span(span, source_map).ok())
+    .collect::<Vec<_>>();
+
+  Ok(ChopOutput { ranges })
+}
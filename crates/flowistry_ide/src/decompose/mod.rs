@@ -30,7 +30,7 @@ pub struct DecomposeOutput {
 pub fn decompose(tcx: TyCtxt, body_id: BodyId) -> Result<DecomposeOutput> {
   let def_id = tcx.hir().body_owner_def_id(body_id);
   let body_with_facts = get_body_with_borrowck_facts(tcx, def_id);
-  let body = &body_with_facts.body;
+  let body = body_with_facts.simplified_body();
   let results = &infoflow::compute_flow(tcx, body_id, body_with_facts);
 
   let source_map = tcx.sess.source_map();
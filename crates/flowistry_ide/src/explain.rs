@@ -0,0 +1,156 @@
+use anyhow::Result;
+use flowistry::{
+  indexed::{impls::LocationDomain, IndexedDomain},
+  infoflow::{self, DependencyReason},
+  mir::{borrowck_facts::get_body_with_borrowck_facts, utils::SpanExt},
+  source_map::{self, EnclosingHirSpans, Range, ToSpan},
+};
+use rustc_hir::BodyId;
+use rustc_middle::ty::TyCtxt;
+use rustc_span::Span;
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+pub struct ExplainStep {
+  pub reason: DependencyReason,
+  pub ranges: Vec<Range>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExplainOutput {
+  pub steps: Vec<ExplainStep>,
+}
+
+/// Explains why the code at `source` is in the slice of the place at `target`, as
+/// the chain of dependencies from the target back to the source.
+pub fn explain(
+  tcx: TyCtxt,
+  body_id: BodyId,
+  target: &Range,
+  source: &Range,
+) -> Result<ExplainOutput> {
+  let def_id = tcx.hir().body_owner_def_id(body_id);
+  let body_with_facts = get_body_with_borrowck_facts(tcx, def_id);
+  let body = body_with_facts.simplified_body();
+  let location_domain = &LocationDomain::new(body);
+
+  let source_map = tcx.sess.source_map();
+  let spanner = source_map::Spanner::new(tcx, body_id, body);
+
+  let target_span = target.to_span(tcx)?;
+  let targets = spanner.span_to_targets(target_span).concat();
+
+  // The source is any location whose span is the smallest to contain the selection
+  let source_span = source.to_span(tcx)?;
+  let location_span = |location| {
+    spanner
+      .location_to_spans(location, location_domain, body, EnclosingHirSpans::None)
+      .into_iter()
+      .filter(|span| span.contains(source_span))
+      .min_by_key(|span| span.size())
+  };
+  let candidates = location_domain
+    .as_vec()
+    .iter()
+    .filter_map(|location| Some((*location, location_span(*location)?)))
+    .collect::<Vec<_>>();
+  let smallest = candidates.iter().map(|(_, span)| span.size()).min();
+  let sources = candidates
+    .iter()
+    .filter(|(_, span)| Some(span.size()) == smallest)
+    .map(|(location, _)| *location)
+    .collect::<Vec<_>>();
+
+  let chain =
+    infoflow::explain_dependency(tcx, body_id, body_with_facts, &targets, &sources)?;
+
+  let steps = chain
+    .into_iter()
+    .map(|step| {
+      let spans = spanner.location_to_spans(
+        step.location,
+        location_domain,
+        body,
+        EnclosingHirSpans::OuterOnly,
+      );
+      let ranges = Span::merge_overlaps(spans)
+        .into_iter()
+        .filter_map(|span| span.trim_leading_whitespace(source_map))
+        .flatten()
+        .filter_map(|span| Range::from_span(span, source_map).ok())
+        .collect::<Vec<_>>();
+      ExplainStep {
+        reason: step.reason,
+        ranges,
+      }
+    })
+    .collect::<Vec<_>>();
+
+  Ok(ExplainOutput { steps })
+}
+
+#[cfg(test)]
+mod test {
+  use flowistry::{
+    source_map::GraphemeIndices,
+    test_utils::{compile_body_with_range, parse_ranges},
+  };
+
+  use super::*;
+
+  fn explain_snippets(input: &str) -> Result<Vec<(DependencyReason, Vec<String>)>> {
+    let (input, ranges) = parse_ranges(input, [("`(", ")`"), ("`[", "]`")])?;
+    let indices = GraphemeIndices::new(&input);
+    let range = |delim| {
+      let (start, end) = ranges[delim][0];
+      Range::from_byte_range(start, end, "dummy.rs", &indices)
+    };
+    let (target, source) = (range("`("), range("`["));
+
+    let mut output = None;
+    compile_body_with_range(input.clone(), target.clone(), &[], |tcx, body_id, _| {
+      output = Some(explain(tcx, body_id, &target, &source).map(|output| {
+        output
+          .steps
+          .into_iter()
+          .map(|step| {
+            let snippets = step.ranges.iter().map(|range| range.substr(&input));
+            (step.reason, snippets.collect())
+          })
+          .collect()
+      }));
+    });
+    output.unwrap()
+  }
+
+  #[test]
+  fn test_explain() {
+    let input = r#"
+fn main() {
+  let a = `[1]`;
+  let b = a;
+  let c = `(b)`;
+}"#;
+    let steps = explain_snippets(input).unwrap();
+    let expected = [
+      (DependencyReason::Data, vec!["let b = a;"]),
+      (DependencyReason::Data, vec!["let a = 1;"]),
+    ];
+    let expected = expected.map(|(reason, snippets)| {
+      (reason, snippets.into_iter().map(String::from).collect())
+    });
+    assert_eq!(steps, expected);
+
+    let input = r#"
+fn main() {
+  let a = 1;
+  let b = `[2]`;
+  let c = `(a)`;
+}"#;
+    let error = explain_snippets(input).unwrap_err();
+    assert_eq!(
+      error.to_string(),
+      "Selection is not in the slice of the target"
+    );
+  }
+}
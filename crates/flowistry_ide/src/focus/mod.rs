@@ -1,23 +1,36 @@
 use anyhow::Result;
 use flowistry::{
-  infoflow::{self, Direction},
+  infoflow::{self, DependencyKind, Direction, FlowDomain, FlowResults},
   mir::{borrowck_facts::get_body_with_borrowck_facts, utils::SpanExt},
-  source_map::{self, Range},
+  source_map::{self, Range, Spanner},
 };
 use itertools::Itertools;
 use rustc_hir::BodyId;
-use rustc_middle::ty::TyCtxt;
+use rustc_middle::{
+  mir::{Body, Location, Place},
+  ty::TyCtxt,
+};
+use rustc_mir_dataflow::JoinSemiLattice;
 use rustc_span::Span;
 use serde::Serialize;
 
 mod direct_influence;
 
+#[derive(Debug, Serialize)]
+pub struct KindInfo {
+  pub kind: DependencyKind,
+  pub slice: Vec<Range>,
+}
+
 #[derive(Debug, Serialize)]
 pub struct PlaceInfo {
   pub range: Range,
   pub ranges: Vec<Range>,
   pub slice: Vec<Range>,
   pub direct_influence: Vec<Range>,
+  /// The parts of the backward slice that the place depends on through each kind
+  /// of dependency other than data, if requested.
+  pub slice_kinds: Vec<KindInfo>,
 }
 
 #[derive(Debug, Serialize)]
@@ -26,11 +39,43 @@ pub struct FocusOutput {
   pub containers: Vec<Range>,
 }
 
-pub fn focus(tcx: TyCtxt, body_id: BodyId) -> Result<FocusOutput> {
+type KindSlices = Vec<(DependencyKind, Vec<Vec<Span>>)>;
+
+/// Computes the focus of each place in the body, along with the parts of each slice of
+/// each kind of dependency if `with_kinds` is true.
+pub fn focus(tcx: TyCtxt, body_id: BodyId, with_kinds: bool) -> Result<FocusOutput> {
   let def_id = tcx.hir().body_owner_def_id(body_id);
   let body_with_facts = get_body_with_borrowck_facts(tcx, def_id);
-  let body = &body_with_facts.body;
-  let results = &infoflow::compute_flow(tcx, body_id, body_with_facts);
+  let body = body_with_facts.simplified_body();
+  if with_kinds {
+    let results = &infoflow::compute_flow_with_kinds(tcx, body_id, body_with_facts);
+    focus_results(tcx, body_id, body, results, |targets, spanner| {
+      DependencyKind::INDIRECT
+        .into_iter()
+        .map(|kind| {
+          let slices = infoflow::compute_dependency_kind_spans(
+            results,
+            targets.clone(),
+            kind,
+            spanner,
+          );
+          (kind, slices)
+        })
+        .collect()
+    })
+  } else {
+    let results = &infoflow::compute_flow(tcx, body_id, body_with_facts);
+    focus_results(tcx, body_id, body, results, |_, _| Vec::new())
+  }
+}
+
+fn focus_results<'tcx, D: FlowDomain<'tcx> + JoinSemiLattice>(
+  tcx: TyCtxt<'tcx>,
+  body_id: BodyId,
+  body: &Body<'tcx>,
+  results: &FlowResults<'_, 'tcx, D>,
+  compute_kind_slices: impl Fn(Vec<Vec<(Place<'tcx>, Location)>>, &Spanner) -> KindSlices,
+) -> Result<FocusOutput> {
   let location_domain = results.analysis.location_domain();
 
   let source_map = tcx.sess.source_map();
@@ -57,7 +102,9 @@ pub fn focus(tcx: TyCtxt, body_id: BodyId) -> Result<FocusOutput> {
   let targets = grouped_spans
     .iter()
     .map(|(_, target)| target.clone())
-    .collect();
+    .collect::<Vec<_>>();
+
+  let kind_slices = compute_kind_slices(targets.clone(), &spanner);
 
   let relevant =
     infoflow::compute_dependency_spans(results, targets, Direction::Both, &spanner);
@@ -67,7 +114,8 @@ pub fn focus(tcx: TyCtxt, body_id: BodyId) -> Result<FocusOutput> {
   let slices = grouped_spans
     .iter()
     .zip(relevant)
-    .filter_map(|((mir_span, targets), relevant)| {
+    .enumerate()
+    .filter_map(|(i, ((mir_span, targets), relevant))| {
       log::debug!("Slice for {mir_span:?} is {relevant:#?}");
 
       let direct_influence = targets
@@ -94,11 +142,20 @@ pub fn focus(tcx: TyCtxt, body_id: BodyId) -> Result<FocusOutput> {
           .collect::<Vec<_>>()
       };
 
+      let slice_kinds = kind_slices
+        .iter()
+        .map(|(kind, slices)| KindInfo {
+          kind: *kind,
+          slice: to_ranges(slices[i].clone()),
+        })
+        .collect::<Vec<_>>();
+
       Some(PlaceInfo {
         range: Range::from_span(mir_span.span(), source_map).ok()?,
         ranges: to_ranges(vec![mir_span.span()]),
         slice: to_ranges(slice),
         direct_influence: to_ranges(direct_influence),
+        slice_kinds,
       })
     })
     .collect::<Vec<_>>();
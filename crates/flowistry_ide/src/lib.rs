@@ -21,11 +21,15 @@ extern crate rustc_mir_dataflow;
 extern crate rustc_serialize;
 extern crate rustc_span;
 
+mod chop;
 #[cfg(feature = "decompose")]
 mod decompose;
+mod explain;
 mod focus;
 mod playground;
 mod plugin;
+mod reduce;
 mod spans;
+mod summarize;
 
 pub use plugin::FlowistryPlugin;
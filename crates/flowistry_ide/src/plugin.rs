@@ -8,7 +8,11 @@ use std::{
 use anyhow::Context;
 use clap::{Parser, Subcommand};
 use flowistry::{
-  extensions::{ContextMode, EvalMode, MutabilityMode, PointerMode, EVAL_MODE},
+  extensions::{
+    ContextMode, ControlMode, EvalMode, ExitMode, GlobalsMode, InteriorMutabilityMode,
+    MutabilityMode, PointerMode, SliceMode, EVAL_MODE,
+  },
+  infoflow::{cache::CACHE_DIR, models, summary::SUMMARY_DIR},
   mir::borrowck_facts,
   source_map::{self, FunctionIdentifier, GraphemeIndices, Range, ToSpan},
   timer::elapsed,
@@ -35,6 +39,28 @@ pub struct FlowistryPluginArgs {
   mutability_mode: Option<MutabilityMode>,
   #[clap(long)]
   pointer_mode: Option<PointerMode>,
+  #[clap(long)]
+  control_mode: Option<ControlMode>,
+  #[clap(long)]
+  exit_mode: Option<ExitMode>,
+  #[clap(long)]
+  interior_mutability_mode: Option<InteriorMutabilityMode>,
+  #[clap(long)]
+  globals_mode: Option<GlobalsMode>,
+  #[clap(long)]
+  slice_mode: Option<SliceMode>,
+  #[clap(long)]
+  depth_limit: Option<usize>,
+
+  /// Disables the on-disk cache of analysis results
+  #[clap(long)]
+  no_cache: bool,
+
+  #[clap(skip)]
+  cache_dir: Option<PathBuf>,
+
+  #[clap(skip)]
+  summary_dir: Option<PathBuf>,
 
   #[clap(subcommand)]
   command: FlowistryCommand,
@@ -53,6 +79,14 @@ enum FlowistryCommand {
     file: String,
     pos: usize,
 
+    /// Computes thin slices, which exclude the code computing pointers
+    #[clap(long)]
+    thin: bool,
+
+    /// Also computes the parts of each slice that come from each kind of dependency
+    #[clap(long)]
+    kinds: bool,
+
     #[clap(last = true)]
     flags: Vec<String>,
   },
@@ -65,6 +99,38 @@ enum FlowistryCommand {
     flags: Vec<String>,
   },
 
+  /// Computes the code on any path of dependencies from the selection at `source`
+  /// to the selection at `sink`
+  Chop {
+    file: String,
+    source: usize,
+    sink: usize,
+
+    #[clap(last = true)]
+    flags: Vec<String>,
+  },
+
+  /// Explains why the code at `source` is in the slice of the selection at `pos`
+  Explain {
+    file: String,
+    pos: usize,
+    source: usize,
+
+    #[clap(last = true)]
+    flags: Vec<String>,
+  },
+
+  /// Writes the backward slice of the selection at `pos` to `output`, as a copy of
+  /// `file` without the statements outside the slice
+  Reduce {
+    file: String,
+    pos: usize,
+    output: PathBuf,
+
+    #[clap(last = true)]
+    flags: Vec<String>,
+  },
+
   Playground {
     file: String,
     start: usize,
@@ -74,6 +140,10 @@ enum FlowistryCommand {
     flags: Vec<String>,
   },
 
+  /// Exports the flow summaries of every crate in the workspace, which are then
+  /// used to analyze calls into those crates
+  Summarize,
+
   Preload,
 
   RustcVersion,
@@ -87,8 +157,26 @@ impl RustcPlugin for FlowistryPlugin {
     "flowistry-driver".into()
   }
 
+  // Flow annotations are enabled for every crate that uses them, not just the
+  // analyzed crate, so that annotated functions are recognized at call sites.
+  // Other crates are compiled as usual, so that `cfg(flowistry)` only affects
+  // crates that opt into annotations.
+  fn driver_args(&self, compiler_args: &[String]) -> Vec<String> {
+    let uses_annotations = compiler_args
+      .windows(2)
+      .any(|args| args[0] == "--extern" && args[1].starts_with("flowistry_annotations="));
+    if !uses_annotations {
+      return Vec::new();
+    }
+
+    models::ANNOTATION_RUSTC_ARGS
+      .iter()
+      .map(|arg| arg.to_string())
+      .collect()
+  }
+
   fn args(&self, target_dir: &Utf8Path) -> RustcPluginArgs<FlowistryPluginArgs> {
-    let args = FlowistryPluginArgs::parse_from(env::args().skip(1));
+    let mut args = FlowistryPluginArgs::parse_from(env::args().skip(1));
 
     let cargo_path = env::var("CARGO_PATH").unwrap_or_else(|_| "cargo".to_string());
 
@@ -113,18 +201,40 @@ impl RustcPlugin for FlowistryPlugin {
       _ => {}
     };
 
+    args.summary_dir = Some(target_dir.join("summaries").into_std_path_buf());
+
+    if let Summarize = &args.command {
+      return RustcPluginArgs {
+        flags: None,
+        file: None,
+        args,
+        cargo_args: vec![],
+      };
+    }
+
     let (file, flags) = match &args.command {
       Spans { file, flags } => (file, flags),
       Focus { file, flags, .. } => (file, flags),
       Decompose { file, flags, .. } => (file, flags),
+      Chop { file, flags, .. } => (file, flags),
+      Explain { file, flags, .. } => (file, flags),
+      Reduce { file, flags, .. } => (file, flags),
       Playground { file, flags, .. } => (file, flags),
       _ => unreachable!(),
     };
 
+    let flags = Some(flags.clone());
+    let file = Some(PathBuf::from(file));
+
+    if !args.no_cache {
+      args.cache_dir = Some(target_dir.join("flowistry").into_std_path_buf());
+    }
+
     RustcPluginArgs {
-      flags: Some(flags.clone()),
-      file: Some(PathBuf::from(file)),
+      flags,
+      file,
       args,
+      cargo_args: vec![],
     }
   }
 
@@ -133,30 +243,119 @@ impl RustcPlugin for FlowistryPlugin {
     compiler_args: Vec<String>,
     plugin_args: FlowistryPluginArgs,
   ) -> RustcResult<()> {
+    use FlowistryCommand::*;
+    let default_context_mode = match plugin_args.command {
+      Summarize => ContextMode::Summary,
+      _ => ContextMode::SigOnly,
+    };
+    let slice_mode = match plugin_args.command {
+      Focus { thin: true, .. } => SliceMode::Thin,
+      _ => plugin_args.slice_mode.unwrap_or(SliceMode::Full),
+    };
     let eval_mode = EvalMode {
-      context_mode: plugin_args.context_mode.unwrap_or(ContextMode::SigOnly),
+      context_mode: plugin_args.context_mode.unwrap_or(default_context_mode),
       mutability_mode: plugin_args
         .mutability_mode
         .unwrap_or(MutabilityMode::DistinguishMut),
       pointer_mode: plugin_args.pointer_mode.unwrap_or(PointerMode::Precise),
+      control_mode: plugin_args
+        .control_mode
+        .unwrap_or(ControlMode::DataAndControl),
+      exit_mode: plugin_args.exit_mode.unwrap_or(ExitMode::ReturnOnly),
+      interior_mutability_mode: plugin_args
+        .interior_mutability_mode
+        .unwrap_or(InteriorMutabilityMode::Ignore),
+      globals_mode: plugin_args.globals_mode.unwrap_or(GlobalsMode::Ignore),
+      slice_mode,
+      depth_limit: plugin_args
+        .depth_limit
+        .unwrap_or_else(|| EvalMode::default().depth_limit),
     };
     fluid_set!(EVAL_MODE, eval_mode);
 
-    use FlowistryCommand::*;
+    let dirs = AnalysisDirs {
+      cache_dir: plugin_args.cache_dir,
+      summary_dir: plugin_args.summary_dir,
+    };
+
     match plugin_args.command {
       Spans { file, .. } => postprocess(crate::spans::spans(&compiler_args, file)),
+      Summarize => postprocess(crate::summarize::summarize(
+        &compiler_args,
+        eval_mode,
+        dirs.summary_dir.unwrap(),
+      )),
       Playground {
         file, start, end, ..
       } => {
         let indices = GraphemeIndices::from_path(&file).unwrap();
         let range = Range::from_char_range(start, end, &file, &indices);
-        postprocess(run(crate::playground::playground, range, &compiler_args))
+        postprocess(run(
+          crate::playground::playground,
+          range,
+          &compiler_args,
+          dirs,
+        ))
       }
-      Focus { file, pos, .. } => {
+      Focus {
+        file, pos, kinds, ..
+      } => {
         let indices = GraphemeIndices::from_path(&file).unwrap();
         let id =
           FunctionIdentifier::Range(Range::from_char_range(pos, pos, &file, &indices));
-        postprocess(run(crate::focus::focus, id, &compiler_args))
+        postprocess(run(
+          move |tcx: TyCtxt, body_id: BodyId| crate::focus::focus(tcx, body_id, kinds),
+          id,
+          &compiler_args,
+          dirs,
+        ))
+      }
+      Chop {
+        file, source, sink, ..
+      } => {
+        let indices = GraphemeIndices::from_path(&file).unwrap();
+        let source = Range::from_char_range(source, source, &file, &indices);
+        let sink = Range::from_char_range(sink, sink, &file, &indices);
+        let id = FunctionIdentifier::Range(source.clone());
+        postprocess(run(
+          move |tcx: TyCtxt, body_id: BodyId| {
+            crate::chop::chop(tcx, body_id, &source, &sink)
+          },
+          id,
+          &compiler_args,
+          dirs,
+        ))
+      }
+      Explain {
+        file, pos, source, ..
+      } => {
+        let indices = GraphemeIndices::from_path(&file).unwrap();
+        let target = Range::from_char_range(pos, pos, &file, &indices);
+        let source = Range::from_char_range(source, source, &file, &indices);
+        let id = FunctionIdentifier::Range(target.clone());
+        postprocess(run(
+          move |tcx: TyCtxt, body_id: BodyId| {
+            crate::explain::explain(tcx, body_id, &target, &source)
+          },
+          id,
+          &compiler_args,
+          dirs,
+        ))
+      }
+      Reduce {
+        file, pos, output, ..
+      } => {
+        let indices = GraphemeIndices::from_path(&file).unwrap();
+        let target = Range::from_char_range(pos, pos, &file, &indices);
+        let id = FunctionIdentifier::Range(target.clone());
+        postprocess(run(
+          move |tcx: TyCtxt, body_id: BodyId| {
+            crate::reduce::reduce(tcx, body_id, &target, &output)
+          },
+          id,
+          &compiler_args,
+          dirs,
+        ))
       }
       Decompose {
         file: _file,
@@ -172,6 +371,7 @@ impl RustcPlugin for FlowistryPlugin {
               crate::decompose::decompose,
               id,
               &compiler_args,
+              dirs,
             ))
           } else {
             panic!("Flowistry must be built with the decompose feature")
@@ -214,10 +414,17 @@ pub fn run_with_callbacks(
   compiler.run().map_err(|_| FlowistryError::BuildError)
 }
 
+/// Directories shared between runs of Flowistry.
+struct AnalysisDirs {
+  cache_dir: Option<PathBuf>,
+  summary_dir: Option<PathBuf>,
+}
+
 fn run<A: FlowistryAnalysis, T: ToSpan>(
   analysis: A,
   target: T,
   args: &[String],
+  dirs: AnalysisDirs,
 ) -> FlowistryResult<A::Output> {
   let mut callbacks = FlowistryCallbacks {
     analysis: Some(analysis),
@@ -225,6 +432,7 @@ fn run<A: FlowistryAnalysis, T: ToSpan>(
     output: None,
     rustc_start: Instant::now(),
     eval_mode: EVAL_MODE.copied(),
+    dirs,
   };
 
   info!("Starting rustc analysis...");
@@ -270,6 +478,7 @@ struct FlowistryCallbacks<A: FlowistryAnalysis, T: ToSpan> {
   output: Option<anyhow::Result<A::Output>>,
   rustc_start: Instant,
   eval_mode: Option<EvalMode>,
+  dirs: AnalysisDirs,
 }
 
 impl<A: FlowistryAnalysis, T: ToSpan> rustc_driver::Callbacks
@@ -291,12 +500,20 @@ impl<A: FlowistryAnalysis, T: ToSpan> rustc_driver::Callbacks
     queries.global_ctxt().unwrap().take().enter(|tcx| {
       elapsed("global_ctxt", start);
       let mut analysis = self.analysis.take().unwrap();
-      self.output = Some((|| {
+      let mut analyze = || -> anyhow::Result<A::Output> {
         let target = self.target.to_span(tcx)?;
         let mut bodies = source_map::find_enclosing_bodies(tcx, target);
         let body = bodies.next().context("Selection did not map to a body")?;
         analysis.analyze(tcx, body)
-      })());
+      };
+      let mut analyze_with_summaries = || match &self.dirs.summary_dir {
+        Some(summary_dir) => SUMMARY_DIR.set(summary_dir, &mut analyze),
+        None => analyze(),
+      };
+      self.output = Some(match &self.dirs.cache_dir {
+        Some(cache_dir) => CACHE_DIR.set(cache_dir, analyze_with_summaries),
+        None => analyze_with_summaries(),
+      });
     });
 
     rustc_driver::Compilation::Stop
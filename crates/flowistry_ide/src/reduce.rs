@@ -0,0 +1,42 @@
+use std::{fs, path::Path};
+
+use anyhow::{Context, Result};
+use flowistry::{
+  infoflow,
+  mir::borrowck_facts::get_body_with_borrowck_facts,
+  source_map::{self, Range, ToSpan},
+};
+use rustc_hir::BodyId;
+use rustc_middle::ty::TyCtxt;
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+pub struct ReduceOutput {
+  pub output: String,
+}
+
+/// Writes the executable backward slice of the place at `target` to `output`, i.e. the
+/// source file with every statement outside the slice removed.
+pub fn reduce(
+  tcx: TyCtxt,
+  body_id: BodyId,
+  target: &Range,
+  output: &Path,
+) -> Result<ReduceOutput> {
+  let def_id = tcx.hir().body_owner_def_id(body_id);
+  let body_with_facts = get_body_with_borrowck_facts(tcx, def_id);
+  let body = body_with_facts.simplified_body();
+  let results = &infoflow::compute_flow(tcx, body_id, body_with_facts);
+  let spanner = source_map::Spanner::new(tcx, body_id, body);
+
+  let target_span = target.to_span(tcx)?;
+  let targets = spanner.span_to_targets(target_span).concat();
+
+  let reduced = infoflow::compute_executable_slice(results, body_id, targets, &spanner)?;
+  fs::write(output, reduced)
+    .with_context(|| format!("Could not write to {}", output.display()))?;
+
+  Ok(ReduceOutput {
+    output: output.display().to_string(),
+  })
+}
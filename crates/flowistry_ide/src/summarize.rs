@@ -0,0 +1,60 @@
+use std::path::PathBuf;
+
+use flowistry::{
+  extensions::{EvalMode, EVAL_MODE},
+  infoflow::summary::{self, SUMMARY_DIR},
+  mir::borrowck_facts,
+};
+use fluid_let::fluid_set;
+
+use crate::plugin::{FlowistryError, FlowistryResult};
+
+struct Callbacks {
+  eval_mode: EvalMode,
+  summary_dir: PathBuf,
+  output: Option<anyhow::Result<()>>,
+}
+
+impl rustc_driver::Callbacks for Callbacks {
+  fn config(&mut self, config: &mut rustc_interface::Config) {
+    config.override_queries = Some(borrowck_facts::override_queries);
+  }
+
+  // Unlike the other commands, compilation continues after the analysis so that
+  // the crate's metadata is available to the crates that depend on it
+  fn after_analysis<'tcx>(
+    &mut self,
+    _compiler: &rustc_interface::interface::Compiler,
+    queries: &'tcx rustc_interface::Queries<'tcx>,
+  ) -> rustc_driver::Compilation {
+    fluid_set!(EVAL_MODE, self.eval_mode);
+
+    queries.global_ctxt().unwrap().peek_mut().enter(|tcx| {
+      // Summaries of the crate's dependencies are read from the same directory
+      // that the crate's summaries are written to
+      let summary_dir = &self.summary_dir;
+      self.output = Some(
+        SUMMARY_DIR.set(summary_dir, || summary::export_summaries(tcx, summary_dir)),
+      );
+    });
+
+    rustc_driver::Compilation::Continue
+  }
+}
+
+pub fn summarize(
+  args: &[String],
+  eval_mode: EvalMode,
+  summary_dir: PathBuf,
+) -> FlowistryResult<()> {
+  let mut callbacks = Callbacks {
+    eval_mode,
+    summary_dir,
+    output: None,
+  };
+  crate::plugin::run_with_callbacks(args, &mut callbacks)?;
+  callbacks
+    .output
+    .unwrap()
+    .map_err(|e| FlowistryError::AnalysisError(e.to_string()))
+}
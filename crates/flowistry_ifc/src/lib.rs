@@ -38,6 +38,7 @@ impl RustcPlugin for IfcPlugin {
       args: (),
       file: None,
       flags: None,
+      cargo_args: vec![],
     }
   }
 
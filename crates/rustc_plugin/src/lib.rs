@@ -9,7 +9,7 @@ extern crate rustc_driver;
 extern crate rustc_interface;
 
 use std::{
-  env,
+  env, fs,
   ops::Deref,
   path::{Path, PathBuf},
   process::{exit, Command},
@@ -70,6 +70,12 @@ pub trait RustcPlugin: Sized {
 
   fn args(&self, target_dir: &Utf8Path) -> RustcPluginArgs<Self::Args>;
 
+  /// Extra arguments passed to rustc when compiling a crate with the arguments
+  /// `compiler_args`, whether or not the plugin runs on the crate.
+  fn driver_args(&self, _compiler_args: &[String]) -> Vec<String> {
+    Vec::new()
+  }
+
   fn run(
     self,
     compiler_args: Vec<String>,
@@ -108,7 +114,7 @@ pub fn cli_main<T: RustcPlugin>(plugin: T) {
   cmd
     .env("RUSTC_WORKSPACE_WRAPPER", path)
     .args(&["check", "-q", "--target-dir"])
-    .arg(target_dir)
+    .arg(&target_dir)
     .args(&args.cargo_args);
 
   let workspace_members = metadata
@@ -201,6 +207,12 @@ pub fn cli_main<T: RustcPlugin>(plugin: T) {
       target.name
     );
   } else {
+    // Workspace members that are already checked would be skipped by Cargo,
+    // so their fingerprints are removed to ensure that the plugin runs on all of them
+    for pkg in &workspace_members {
+      remove_fingerprints(&target_dir, &pkg.name);
+    }
+
     cmd.arg("--all");
   }
 
@@ -229,6 +241,35 @@ pub fn cli_main<T: RustcPlugin>(plugin: T) {
   exit(exit_status.code().unwrap_or(-1));
 }
 
+/// Removes Cargo's fingerprints of `package` from every profile in `target_dir`,
+/// which makes Cargo rebuild the package without touching its dependencies.
+fn remove_fingerprints(target_dir: &Utf8Path, package: &str) {
+  let profiles = match target_dir.read_dir() {
+    Ok(profiles) => profiles,
+    Err(_) => return,
+  };
+  let prefix = format!("{package}-");
+  for profile in profiles.filter_map(Result::ok) {
+    let fingerprints = match profile.path().join(".fingerprint").read_dir() {
+      Ok(fingerprints) => fingerprints,
+      Err(_) => continue,
+    };
+    for fingerprint in fingerprints.filter_map(Result::ok) {
+      // Fingerprints are named "{package}-{hash}", where the hash has no dashes
+      let name = fingerprint.file_name();
+      let is_package = name
+        .to_str()
+        .and_then(|name| name.strip_prefix(&prefix))
+        .map_or(false, |hash| !hash.contains('-'));
+      if is_package {
+        if let Err(err) = fs::remove_dir_all(fingerprint.path()) {
+          log::warn!("Failed to remove fingerprint of package {package}: {err}");
+        }
+      }
+    }
+  }
+}
+
 pub fn driver_main<T: RustcPlugin>(plugin: T) {
   rustc_driver::init_rustc_env_logger();
 
@@ -307,6 +348,11 @@ pub fn driver_main<T: RustcPlugin>(plugin: T) {
     let normal_rustc = args.iter().any(|arg| arg.starts_with("--print"));
     let run_plugin = primary_package && !normal_rustc;
 
+    if !normal_rustc {
+      let driver_args = plugin.driver_args(&args);
+      args.extend(driver_args);
+    }
+
     if run_plugin {
       let plugin_args: T::Args =
         serde_json::from_str(&env::var(PLUGIN_ARGS).unwrap()).unwrap();
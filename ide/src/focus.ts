@@ -12,11 +12,19 @@ interface Spans {
   spans: Range[];
 }
 
+type DependencyKind = "Data" | "Control" | "Alias" | "Call";
+
+interface KindInfo {
+  kind: DependencyKind;
+  slice: Range[];
+}
+
 interface PlaceInfo {
   range: Range;
   ranges: Range[];
   slice: Range[];
   direct_influence: Range[];
+  slice_kinds: KindInfo[];
 }
 
 interface Focus {